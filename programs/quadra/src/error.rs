@@ -100,4 +100,121 @@ pub enum ErrorCode {
 
     #[msg("Invalid token account")]
     AlreadyMember,
+
+    #[msg("Votes were already cast on the other side.")]
+    VoteSideMismatch,
+
+    #[msg("No votes have been cast.")]
+    NoVotesCast,
+
+    #[msg("Arithmetic overflow.")]
+    MathOverflow,
+
+    #[msg("User is not an active member of the DAO.")]
+    NotActiveMember,
+
+    #[msg("Basis points must be between 0 and 10000.")]
+    InvalidBasisPoints,
+
+    #[msg("Creator has reached the maximum number of DAOs.")]
+    TooManyDaos,
+
+    #[msg("Proposal already has votes.")]
+    ProposalAlreadyVoted,
+
+    #[msg("Proposal has not passed.")]
+    ProposalNotPassed,
+
+    #[msg("Proposal has reached the maximum number of instructions.")]
+    TooManyInstructions,
+
+    #[msg("Proposal instructions must be executed in order.")]
+    InstructionOutOfOrder,
+
+    #[msg("Proposal has been cancelled.")]
+    ProposalCancelled,
+
+    #[msg("Proposal must be migrated with migrate_proposal.")]
+    ProposalNotMigrated,
+
+    #[msg("Proposal is already migrated.")]
+    ProposalAlreadyMigrated,
+
+    #[msg("Invalid execution delay.")]
+    InvalidExecutionDelay,
+
+    #[msg("The execution delay has not passed.")]
+    ProposalTimelocked,
+
+    #[msg("Proposal must be finalized first.")]
+    ProposalNotFinalized,
+
+    #[msg("Proposal is already finalized.")]
+    ProposalAlreadyFinalized,
+
+    #[msg("Invalid number of proposal options.")]
+    InvalidOptionCount,
+
+    #[msg("Invalid proposal option label.")]
+    InvalidOptionLabel,
+
+    #[msg("Invalid proposal option.")]
+    InvalidOption,
+
+    #[msg("Invalid voice credit config.")]
+    InvalidCreditConfig,
+
+    #[msg("Not enough voice credits.")]
+    InsufficientVoiceCredits,
+
+    #[msg("The voice credits account is required.")]
+    VoiceCreditsRequired,
+
+    #[msg("Invalid admission policy.")]
+    InvalidAdmissionPolicy,
+
+    #[msg("The wallet is not admitted to the DAO.")]
+    NotAdmitted,
+
+    #[msg("Votes on snapshot proposals need a snapshot proof.")]
+    SnapshotVoteRequired,
+
+    #[msg("Proposal has no snapshot.")]
+    NoSnapshot,
+
+    #[msg("Invalid snapshot proof.")]
+    InvalidSnapshotProof,
+
+    #[msg("The votes cost more than the snapshot weight.")]
+    SnapshotWeightExceeded,
+
+    #[msg("The votes were paid by another wallet.")]
+    VotePayerMismatch,
+
+    #[msg("Invalid delegate.")]
+    InvalidDelegate,
+
+    #[msg("Not enough staked tokens.")]
+    InsufficientStake,
+
+    #[msg("Invalid DAO account.")]
+    InvalidDao,
+
+    #[msg("DAO must be migrated with migrate_dao.")]
+    DaoNotMigrated,
+
+    #[msg("DAO is already migrated.")]
+    DaoAlreadyMigrated,
+
+    #[msg("Invalid vote record.")]
+    InvalidVoteRecord,
+
+    #[msg("Vote record is already migrated.")]
+    VoteRecordAlreadyMigrated,
+
+    #[msg("The side of the legacy votes cannot be recovered from the proposal.")]
+    UnknownVoteSide,
+
+    #[msg("The NFT already admitted another wallet.")]
+    AdmissionAlreadyClaimed,
 }
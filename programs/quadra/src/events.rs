@@ -0,0 +1,10 @@
+use anchor_lang::prelude::*;
+
+use crate::state::DaoConfig;
+
+#[event]
+pub struct DaoConfigUpdated {
+    pub dao: Pubkey,
+    pub old_config: DaoConfig,
+    pub new_config: DaoConfig,
+}
@@ -0,0 +1,102 @@
+use anchor_lang::prelude::*;
+
+use crate::error;
+use crate::state::{ Admin, DAO, ADMIN_SEED, MAX_ADMINS, MIN_ADMINS, DAO_VERSION };
+
+pub fn add_admin(ctx: Context<AddAdmin>) -> Result<()> {
+    let dao = &mut ctx.accounts.dao;
+    let new_admin = &mut ctx.accounts.new_admin;
+
+    ctx.accounts.admin.is_admin(&dao.key(), ctx.accounts.user.key)?;
+
+    if dao.admin_count >= MAX_ADMINS {
+        return Err(error::ErrorCode::InvalidAdmins.into());
+    }
+
+    new_admin.dao = dao.key();
+    new_admin.admin = ctx.accounts.new_admin_user.key();
+
+    dao.admin_count = dao.admin_count
+        .checked_add(1)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+pub fn remove_admin(ctx: Context<RemoveAdmin>) -> Result<()> {
+    let dao = &mut ctx.accounts.dao;
+
+    ctx.accounts.admin.is_admin(&dao.key(), ctx.accounts.user.key)?;
+
+    // a DAO must always keep at least one admin
+    if dao.admin_count <= MIN_ADMINS {
+        return Err(error::ErrorCode::InvalidAdmins.into());
+    }
+
+    dao.admin_count = dao.admin_count
+        .checked_sub(1)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddAdmin<'info> {
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Box<Account<'info, Admin>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [ADMIN_SEED, dao.key().as_ref(), new_admin_user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<Admin>()
+    )]
+    pub new_admin: Box<Account<'info, Admin>>,
+
+    /// CHECK: Only used to derive the new admin account.
+    pub new_admin_user: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAdmin<'info> {
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Box<Account<'info, Admin>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [ADMIN_SEED, dao.key().as_ref(), removed_admin_user.key().as_ref()],
+        bump
+    )]
+    pub removed_admin: Box<Account<'info, Admin>>,
+
+    /// CHECK: Only used to derive the removed admin account.
+    pub removed_admin_user: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
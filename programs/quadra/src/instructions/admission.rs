@@ -0,0 +1,116 @@
+use anchor_lang::prelude::*;
+
+use crate::error;
+use crate::state::{ Admin, AdmissionEntry, AdmissionPolicy, DAO, ADMIN_SEED, ADMISSION_SEED, DAO_VERSION };
+
+// Admits a wallet to a DAO with the allowlist policy (signed by an admin)
+// or the attestation policy (signed by the configured issuer)
+pub fn add_admission_entry(ctx: Context<AddAdmissionEntry>) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let admission_entry = &mut ctx.accounts.admission_entry;
+    let user = &ctx.accounts.user;
+
+    match dao.admission_policy {
+        AdmissionPolicy::Allowlist => {
+            match &ctx.accounts.admin {
+                Some(admin) => admin.is_admin(&dao.key(), user.key)?,
+                None => {
+                    return Err(error::ErrorCode::NotAuthorized.into());
+                }
+            }
+        }
+        AdmissionPolicy::Attestation => {
+            if user.key() != dao.admission_key {
+                return Err(error::ErrorCode::NotAuthorized.into());
+            }
+        }
+        _ => {
+            return Err(error::ErrorCode::InvalidAdmissionPolicy.into());
+        }
+    }
+
+    admission_entry.dao = dao.key();
+    admission_entry.user = ctx.accounts.member.key();
+    admission_entry.issuer = user.key();
+    admission_entry.policy = dao.admission_policy;
+
+    Ok(())
+}
+
+// Revokes an admission, existing memberships are moderated with expel_member
+pub fn remove_admission_entry(ctx: Context<RemoveAdmissionEntry>) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let user = &ctx.accounts.user;
+
+    match &ctx.accounts.admin {
+        Some(admin) => admin.is_admin(&dao.key(), user.key)?,
+        None => {
+            if ctx.accounts.admission_entry.issuer != user.key() {
+                return Err(error::ErrorCode::NotAuthorized.into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AddAdmissionEntry<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [ADMISSION_SEED, dao.key().as_ref(), member.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<AdmissionEntry>()
+    )]
+    pub admission_entry: Box<Account<'info, AdmissionEntry>>,
+
+    /// CHECK: Only used to derive the admission entry.
+    pub member: AccountInfo<'info>,
+
+    // only required for the allowlist policy
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Option<Box<Account<'info, Admin>>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveAdmissionEntry<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [ADMISSION_SEED, dao.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub admission_entry: Box<Account<'info, AdmissionEntry>>,
+
+    /// CHECK: Only used to derive the admission entry.
+    pub member: AccountInfo<'info>,
+
+    // only required when an admin removes the entry
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Option<Box<Account<'info, Admin>>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
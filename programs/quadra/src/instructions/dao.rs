@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{ Mint, Token, TokenAccount, Transfer, transfer };
+use anchor_spl::token::{ Mint, Token, TokenAccount, Transfer, transfer, Burn, burn };
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_lang::system_program;
 use solana_program::{ pubkey, pubkey::Pubkey };
@@ -9,6 +9,7 @@ use crate::error;
 use crate::state::{
     Membership,
     Admin,
+    CreatorRegistry,
     DAO,
     FeeAccount,
     BURN_SEED,
@@ -19,7 +20,23 @@ use crate::state::{
     MEMBERSHIP_SEED,
     TREASURY_VAULT_SEED,
     ADMIN_SEED,
+    CREATOR_REGISTRY_SEED,
+    MAX_DAOS_PER_CREATOR,
+    DaoConfigArgs,
+    QuorumMode,
+    CreditMode,
+    AdmissionPolicy,
+    AdmissionEntry,
+    AdmissionClaim,
+    ADMISSION_SEED,
+    ADMISSION_CLAIM_SEED,
+    METADATA_SEED,
+    METADATA_PROGRAM_ID,
+    read_metadata_collection,
+    MAX_PROPOSAL_ACTIONS,
+    DAO_VERSION,
 };
+use crate::events::DaoConfigUpdated;
 
 pub fn create_dao(
     ctx: Context<CreateDAO>,
@@ -27,21 +44,35 @@ pub fn create_dao(
     image: String,
     min_yes_votes: u64,
     proposal_creation_fee: u64,
-    membership_fee: u64
+    membership_fee: u64,
+    leave_refund_bps: u16
 ) -> Result<()> {
-    if membership_fee < 1 {
-        return Err(error::ErrorCode::InvalidFee.into());
-    }
-
-    if proposal_creation_fee < 1 {
-        return Err(error::ErrorCode::InvalidFee.into());
-    }
-
+    let registry = &mut ctx.accounts.registry;
     let dao = &mut ctx.accounts.dao;
+    let admin = &mut ctx.accounts.admin;
+    let membership = &mut ctx.accounts.membership;
     let fee_account = &ctx.accounts.fee_account;
 
+    DAO::check_fees(proposal_creation_fee, membership_fee, leave_refund_bps)?;
     dao.check_length(&name, &image)?;
 
+    if registry.daos.len() >= MAX_DAOS_PER_CREATOR {
+        return Err(error::ErrorCode::TooManyDaos.into());
+    }
+
+    // the DAO address is derived from the creator and its index in the creator registry
+    registry.creator = ctx.accounts.user.key();
+    registry.daos.push(dao.key());
+
+    // the creator is the initial admin and the first member
+    admin.dao = dao.key();
+    admin.admin = ctx.accounts.user.key();
+
+    membership.dao = dao.key();
+    membership.user = ctx.accounts.user.key();
+    membership.joined_date = Clock::get()?.unix_timestamp;
+    membership.active = true;
+
     dao.creator = *ctx.accounts.user.to_account_info().key;
     dao.name = name;
     dao.image = image;
@@ -51,6 +82,27 @@ pub fn create_dao(
     dao.min_yes_votes = min_yes_votes;
     dao.proposal_creation_fee = proposal_creation_fee;
     dao.membership_fee = membership_fee;
+    dao.leave_refund_bps = leave_refund_bps;
+    dao.admin_count = 1;
+    dao.execution_delay = 0;
+    dao.execution_window = 0;
+    dao.quorum_mode = QuorumMode::None;
+    dao.quorum = 0;
+    dao.member_count = 1;
+    dao.approval_threshold_bps = 0;
+    dao.action_threshold_bps = [0; MAX_PROPOSAL_ACTIONS];
+    dao.large_transfer_amount = 0;
+    dao.large_transfer_threshold_bps = 0;
+    dao.credit_mode = CreditMode::Disabled;
+    dao.credit_allowance = 0;
+    dao.credit_epoch_length = 0;
+    dao.credit_charge_tokens = false;
+    dao.admission_policy = AdmissionPolicy::Open;
+    dao.admission_root = [0; 32];
+    dao.admission_key = Pubkey::default();
+    dao.lockup_boost_bps = 0;
+    dao.governance_mint = ctx.accounts.token_mint.key();
+    dao.version = DAO_VERSION;
 
     // send the creation fee to the fee address
     let cpi_context = CpiContext::new(
@@ -65,15 +117,89 @@ pub fn create_dao(
     Ok(())
 }
 
-pub fn join_dao(ctx: Context<JoinDAO>) -> Result<()> {
+pub fn update_dao_config(ctx: Context<UpdateDAOConfig>, config: DaoConfigArgs) -> Result<()> {
+    let dao = &mut ctx.accounts.dao;
+
+    ctx.accounts.admin.is_admin(&dao.key(), ctx.accounts.user.key)?;
+
+    let old_config = dao.config();
+    dao.apply_config(&config)?;
+
+    emit!(DaoConfigUpdated {
+        dao: dao.key(),
+        old_config,
+        new_config: dao.config(),
+    });
+
+    Ok(())
+}
+
+pub fn join_dao(ctx: Context<JoinDAO>, proof: Vec<[u8; 32]>) -> Result<()> {
     let dao = &mut ctx.accounts.dao;
     let membership = &mut ctx.accounts.membership;
+    let user = ctx.accounts.user.key();
+
+    // one wallet per person only holds when the DAO restricts who can join
+    match dao.admission_policy {
+        AdmissionPolicy::Open => {}
+        AdmissionPolicy::Allowlist | AdmissionPolicy::Attestation => {
+            match &ctx.accounts.admission_entry {
+                Some(admission_entry) => admission_entry.admits(dao, &dao.key(), &user)?,
+                None => {
+                    return Err(error::ErrorCode::NotAdmitted.into());
+                }
+            }
+        }
+        AdmissionPolicy::MerkleAllowlist => {
+            if !dao.verify_admission_proof(&user, &proof) {
+                return Err(error::ErrorCode::NotAdmitted.into());
+            }
+        }
+        AdmissionPolicy::CollectionHolding => {
+            let (token_account, metadata, admission_claim) = match
+                (
+                    &ctx.accounts.admission_token_account,
+                    &ctx.accounts.admission_metadata,
+                    &mut ctx.accounts.admission_claim,
+                )
+            {
+                (Some(token_account), Some(metadata), Some(admission_claim)) =>
+                    (token_account, metadata, admission_claim),
+                _ => {
+                    return Err(error::ErrorCode::NotAdmitted.into());
+                }
+            };
+
+            if token_account.owner != user || token_account.amount == 0 {
+                return Err(error::ErrorCode::NotAdmitted.into());
+            }
+
+            let (metadata_address, _) = Pubkey::find_program_address(
+                &[METADATA_SEED, METADATA_PROGRAM_ID.as_ref(), token_account.mint.as_ref()],
+                &METADATA_PROGRAM_ID
+            );
+
+            if
+                metadata.key() != metadata_address ||
+                *metadata.owner != METADATA_PROGRAM_ID ||
+                read_metadata_collection(&metadata.try_borrow_data()?) !=
+                    Some((token_account.mint, Some(dao.admission_key)))
+            {
+                return Err(error::ErrorCode::NotAdmitted.into());
+            }
+
+            // the NFT can't be passed on to admit other wallets
+            admission_claim.claim(&dao.key(), &token_account.mint, &user)?;
+        }
+    }
 
     membership.dao = *dao.to_account_info().key;
     membership.user = *ctx.accounts.user.to_account_info().key;
     membership.joined_date = Clock::get()?.unix_timestamp;
     membership.active = true;
 
+    dao.add_member()?;
+
     // Transfer the join fee from the user's account to the dao's treasury vault
     let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), Transfer {
         from: ctx.accounts.user_token_mint_account.to_account_info(),
@@ -85,17 +211,138 @@ pub fn join_dao(ctx: Context<JoinDAO>) -> Result<()> {
     Ok(())
 }
 
+// Closes the membership of the user, refunding the share of the membership fee set by the DAO
+pub fn leave_dao(ctx: Context<LeaveDAO>) -> Result<()> {
+    let dao = &mut ctx.accounts.dao;
+    let treasury_vault = &ctx.accounts.treasury_vault;
+
+    ctx.accounts.membership.is_active_member(&dao.key(), ctx.accounts.user.key)?;
+
+    dao.remove_member();
+
+    let refund = dao.leave_refund()?;
+
+    if refund > treasury_vault.amount {
+        return Err(error::ErrorCode::InsufficientTreasuryBalance.into());
+    }
+
+    if refund > 0 {
+        let dao_key = dao.key();
+        let bump = *ctx.bumps.get("treasury_vault").unwrap();
+        let signer: &[&[&[u8]]] = &[&[TREASURY_VAULT_SEED, dao_key.as_ref(), &[bump]]];
+
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: treasury_vault.to_account_info(),
+                to: ctx.accounts.user_token_mint_account.to_account_info(),
+                authority: treasury_vault.to_account_info(),
+            },
+            signer
+        );
+        transfer(cpi_context, refund)?;
+    }
+
+    Ok(())
+}
+
+// Deactivates a member, the membership is kept so the user cannot join again
+pub fn expel_member(ctx: Context<ModerateMember>) -> Result<()> {
+    let dao = &mut ctx.accounts.dao;
+    let membership = &mut ctx.accounts.membership;
+
+    ctx.accounts.admin.is_admin(&dao.key(), ctx.accounts.user.key)?;
+
+    membership.is_active_member(&dao.key(), ctx.accounts.member.key)?;
+
+    membership.active = false;
+
+    dao.remove_member();
+
+    Ok(())
+}
+
+pub fn reactivate_member(ctx: Context<ModerateMember>) -> Result<()> {
+    let dao = &mut ctx.accounts.dao;
+    let membership = &mut ctx.accounts.membership;
+
+    ctx.accounts.admin.is_admin(&dao.key(), ctx.accounts.user.key)?;
+
+    if membership.active {
+        return Err(error::ErrorCode::AlreadyMember.into());
+    }
+
+    membership.active = true;
+
+    dao.add_member()?;
+
+    Ok(())
+}
+
+// Burns the tokens held by the burn vault, anyone can call it
+pub fn flush_burn_vault(ctx: Context<FlushBurnVault>) -> Result<()> {
+    let burn_vault = &ctx.accounts.burn_vault;
+
+    if burn_vault.amount == 0 {
+        return Err(error::ErrorCode::NoTokens.into());
+    }
+
+    let dao_key = ctx.accounts.dao.key();
+    let bump = *ctx.bumps.get("burn_vault").unwrap();
+    let signer: &[&[&[u8]]] = &[&[BURN_SEED, dao_key.as_ref(), &[bump]]];
+
+    let cpi_context = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        Burn {
+            mint: ctx.accounts.token_mint.to_account_info(),
+            from: burn_vault.to_account_info(),
+            authority: burn_vault.to_account_info(),
+        },
+        signer
+    );
+    burn(cpi_context, burn_vault.amount)?;
+
+    Ok(())
+}
+
 #[derive(Accounts)]
 pub struct CreateDAO<'info> {
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [CREATOR_REGISTRY_SEED, user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<CreatorRegistry>() + 32 * MAX_DAOS_PER_CREATOR
+    )]
+    pub registry: Box<Account<'info, CreatorRegistry>>,
+
     #[account(
         init,
         payer = user,
-        seeds = [DAO_SEED, user.key().as_ref()],
+        seeds = [DAO_SEED, user.key().as_ref(), registry.next_dao_index().to_le_bytes().as_ref()],
         bump,
         space = 8 + std::mem::size_of::<DAO>() + MAX_NAME_LENGTH + MAX_IMAGE_LENGTH
     )]
     pub dao: Box<Account<'info, DAO>>,
 
+    #[account(
+        init,
+        payer = user,
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<Admin>()
+    )]
+    pub admin: Box<Account<'info, Admin>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<Membership>()
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
     #[account(
         init,
         seeds = [TREASURY_VAULT_SEED, dao.key().as_ref()],
@@ -133,8 +380,29 @@ pub struct CreateDAO<'info> {
 }
 
 #[derive(Accounts)]
-pub struct JoinDAO<'info> {
+pub struct UpdateDAOConfig<'info> {
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Box<Account<'info, Admin>>,
+
     #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct JoinDAO<'info> {
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
     pub dao: Box<Account<'info, DAO>>,
 
     #[account(
@@ -160,11 +428,135 @@ pub struct JoinDAO<'info> {
     )]
     pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
 
+    // only required for the allowlist and attestation admission policies
+    #[account(
+        seeds = [ADMISSION_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admission_entry: Option<Box<Account<'info, AdmissionEntry>>>,
+
+    // only required for the collection holding admission policy
+    pub admission_token_account: Option<Box<Account<'info, TokenAccount>>>,
+
+    // only required for the collection holding admission policy
+    /// CHECK: Metaplex metadata of the held NFT, it is checked inside the function
+    pub admission_metadata: Option<UncheckedAccount<'info>>,
+
+    // only required for the collection holding admission policy
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + std::mem::size_of::<AdmissionClaim>(),
+        seeds = [
+            ADMISSION_CLAIM_SEED,
+            dao.key().as_ref(),
+            admission_token_account.as_ref().ok_or(error::ErrorCode::NotAdmitted)?.mint.as_ref(),
+        ],
+        bump
+    )]
+    pub admission_claim: Option<Box<Account<'info, AdmissionClaim>>>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
     pub token_mint: Account<'info, Mint>,
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
+
+#[derive(Accounts)]
+pub struct LeaveDAO<'info> {
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_VAULT_SEED, dao.key().as_ref()],
+        bump,
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ModerateMember<'info> {
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), member.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    /// CHECK: The membership would not match the member if it were not correct.
+    pub member: AccountInfo<'info>,
+
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Box<Account<'info, Admin>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FlushBurnVault<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        seeds = [BURN_SEED, dao.key().as_ref()],
+        bump,
+        token::mint = token_mint
+    )]
+    pub burn_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+}
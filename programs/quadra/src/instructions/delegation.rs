@@ -0,0 +1,272 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{ Mint, Token, TokenAccount };
+use anchor_spl::associated_token::AssociatedToken;
+use solana_program::clock::Clock;
+
+use crate::error;
+use crate::instructions::proposal::{ pay_vote_cost, spend_voice_credits };
+use crate::state::{
+    DAO,
+    Delegation,
+    Membership,
+    Proposal,
+    ProposalAction,
+    ProposalStatus,
+    UserProposalVotes,
+    VoiceCredits,
+    VoteSide,
+    DELEGATION_SEED,
+    ESCROW_VAULT_SEED,
+    MEMBERSHIP_SEED,
+    USER_PROPOSAL_VOTES_SEED,
+    VOICE_CREDITS_SEED,
+    PROPOSAL_VERSION,
+    quadratic_cost,
+    DAO_VERSION,
+};
+
+// Assigns the voting power of the member to another wallet, replacing any previous delegate
+pub fn delegate(ctx: Context<Delegate>, delegate: Pubkey) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let delegation = &mut ctx.accounts.delegation;
+    let user = &ctx.accounts.user;
+
+    ctx.accounts.membership.is_active_member(&dao.key(), user.key)?;
+
+    if delegate == user.key() || delegate == Pubkey::default() {
+        return Err(error::ErrorCode::InvalidDelegate.into());
+    }
+
+    // voting power is only delegated between members
+    ctx.accounts.delegate_membership.is_active_member(&dao.key(), &delegate)?;
+
+    delegation.dao = dao.key();
+    delegation.delegator = user.key();
+    delegation.delegate = delegate;
+
+    Ok(())
+}
+
+// Closes the delegation, votes already cast by the delegate stay
+pub fn undelegate(_ctx: Context<Undelegate>) -> Result<()> {
+    Ok(())
+}
+
+// Casts votes for a delegator. The votes are recorded and priced on the vote record of the
+// delegator, so the quadratic cost grows per delegator and not with the delegated total.
+// The delegate pays and gets the deposit back with withdraw_vote_deposit.
+pub fn vote_as_delegate(ctx: Context<VoteAsDelegate>, amount: u64, side: VoteSide) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    let dao = &ctx.accounts.dao;
+    let delegation = &ctx.accounts.delegation;
+    let user_proposal_votes = &mut ctx.accounts.user_proposal_votes;
+    let delegator = &ctx.accounts.delegator;
+    let user = &ctx.accounts.user;
+
+    if proposal.end_date <= Clock::get().unwrap().unix_timestamp {
+        return Err(error::ErrorCode::ProposalEnded.into());
+    }
+
+    if proposal.status == ProposalStatus::Cancelled {
+        return Err(error::ErrorCode::ProposalCancelled.into());
+    }
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.action == ProposalAction::Choice {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    if proposal.has_snapshot() {
+        return Err(error::ErrorCode::SnapshotVoteRequired.into());
+    }
+
+    if delegation.delegate != user.key() {
+        return Err(error::ErrorCode::InvalidDelegate.into());
+    }
+
+    ctx.accounts.membership.is_active_member(&dao.key(), delegator.key)?;
+    ctx.accounts.delegate_membership.is_active_member(&dao.key(), user.key)?;
+
+    if user_proposal_votes.amount > 0 && user_proposal_votes.side != side {
+        return Err(error::ErrorCode::VoteSideMismatch.into());
+    }
+
+    user_proposal_votes.set_payer(user.key)?;
+
+    // the credits of the delegator are spent
+    spend_voice_credits(
+        dao,
+        &mut ctx.accounts.voice_credits,
+        delegator.key(),
+        user_proposal_votes.credits_spent()?,
+        quadratic_cost(user_proposal_votes.amount, amount, 0)?
+    )?;
+
+    let vote_cost = match dao.charges_tokens() {
+        true => user_proposal_votes.vote_cost(amount, ctx.accounts.token_mint.decimals)?,
+        false => 0,
+    };
+
+    if vote_cost > 0 {
+        pay_vote_cost(
+            proposal.burn_on_vote,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.token_mint.to_account_info(),
+            ctx.accounts.user_token_mint_account.to_account_info(),
+            ctx.accounts.escrow_vault.to_account_info(),
+            user.to_account_info(),
+            vote_cost
+        )?;
+    }
+
+    // count each delegator once towards the distinct voters
+    if user_proposal_votes.amount == 0 && amount > 0 {
+        proposal.voter_count = proposal.voter_count
+            .checked_add(1)
+            .ok_or(error::ErrorCode::MathOverflow)?;
+    }
+
+    proposal.add_votes(side, amount)?;
+    user_proposal_votes.add_votes(side, amount, vote_cost)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct Delegate<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [DELEGATION_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<Delegation>()
+    )]
+    pub delegation: Box<Account<'info, Delegation>>,
+
+    #[account(
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    #[account(
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), delegate.as_ref()],
+        bump
+    )]
+    pub delegate_membership: Box<Account<'info, Membership>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Undelegate<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [DELEGATION_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub delegation: Box<Account<'info, Delegation>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct VoteAsDelegate<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        seeds = [DELEGATION_SEED, dao.key().as_ref(), delegator.key().as_ref()],
+        bump
+    )]
+    pub delegation: Box<Account<'info, Delegation>>,
+
+    /// CHECK: The delegation would not match the delegator if it were not correct.
+    pub delegator: AccountInfo<'info>,
+
+    // stores the delegators vote count for the given proposal
+    #[account(
+        init_if_needed,
+        seeds = [USER_PROPOSAL_VOTES_SEED, delegator.key.as_ref(), proposal.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<UserProposalVotes>(),
+        payer = user
+    )]
+    pub user_proposal_votes: Box<Account<'info, UserProposalVotes>>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
+
+    // token account of the delegate paying for the votes
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), delegator.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    #[account(
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub delegate_membership: Box<Account<'info, Membership>>,
+
+    // only required when the DAO uses voice credits per epoch
+    #[account(
+        init_if_needed,
+        seeds = [VOICE_CREDITS_SEED, dao.key().as_ref(), delegator.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<VoiceCredits>(),
+        payer = user
+    )]
+    pub voice_credits: Option<Box<Account<'info, VoiceCredits>>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
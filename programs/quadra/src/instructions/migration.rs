@@ -0,0 +1,249 @@
+use anchor_lang::prelude::*;
+use anchor_lang::{ system_program, Discriminator };
+use anchor_spl::token::{ Mint, Token, TokenAccount };
+
+use crate::error;
+use crate::state::{
+    Admin,
+    LegacyDao,
+    LegacyProposal,
+    LegacyUserProposalVotes,
+    UserProposalVotes,
+    DAO,
+    Proposal,
+    ADMIN_SEED,
+    DAO_VERSION,
+    ESCROW_VAULT_SEED,
+    MAX_DESCRIPTION_LENGTH,
+    MAX_IMAGE_LENGTH,
+    MAX_NAME_LENGTH,
+    MAX_TITLE_LENGTH,
+    PROPOSAL_VERSION,
+    TREASURY_VAULT_SEED,
+    USER_PROPOSAL_VOTES_SEED,
+};
+
+// Rewrites a proposal created before the typed status and action enums into the current layout,
+// growing the account when the current layout needs more space, and creates the escrow vault
+// legacy proposals never had. Anyone can migrate a proposal once its DAO is migrated.
+pub fn migrate_proposal(ctx: Context<MigrateProposal>) -> Result<()> {
+    let proposal_info = ctx.accounts.proposal.to_account_info();
+
+    if proposal_info.owner != ctx.program_id {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    let migrated = {
+        let data = proposal_info.try_borrow_data()?;
+
+        if data.len() < 8 || data[..8] != Proposal::discriminator() {
+            return Err(error::ErrorCode::InvalidProposal.into());
+        }
+
+        if let Ok(proposal) = Proposal::try_deserialize(&mut &data[..]) {
+            if proposal.version == PROPOSAL_VERSION {
+                return Err(error::ErrorCode::ProposalAlreadyMigrated.into());
+            }
+        }
+
+        LegacyProposal::deserialize(&mut &data[8..])?.migrate()?
+    };
+
+    if migrated.dao != ctx.accounts.dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    let space = 8 + std::mem::size_of::<Proposal>() + MAX_TITLE_LENGTH + MAX_DESCRIPTION_LENGTH;
+    grow_account(&proposal_info, &ctx.accounts.user, &ctx.accounts.system_program, space)?;
+
+    let mut data = proposal_info.try_borrow_mut_data()?;
+    migrated.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+// Rewrites a DAO created before the governance settings into the current layout with the default
+// settings. Legacy DAOs have no admins, so only the creator can migrate and becomes the first admin.
+// The program cannot enumerate the memberships, the creator backfills the count of active members.
+pub fn migrate_dao(ctx: Context<MigrateDao>, member_count: u64) -> Result<()> {
+    let dao_info = ctx.accounts.dao.to_account_info();
+
+    if dao_info.owner != ctx.program_id {
+        return Err(error::ErrorCode::InvalidDao.into());
+    }
+
+    let migrated = {
+        let data = dao_info.try_borrow_data()?;
+
+        if data.len() < 8 || data[..8] != DAO::discriminator() {
+            return Err(error::ErrorCode::InvalidDao.into());
+        }
+
+        // a legacy DAO can deserialize into the current layout from its zeroed padding
+        if let Ok(dao) = DAO::try_deserialize(&mut &data[..]) {
+            if dao.version == DAO_VERSION {
+                return Err(error::ErrorCode::DaoAlreadyMigrated.into());
+            }
+        }
+
+        LegacyDao::deserialize(&mut &data[8..])?.migrate(ctx.accounts.treasury_vault.mint, member_count)
+    };
+
+    if migrated.creator != ctx.accounts.user.key() {
+        return Err(error::ErrorCode::NotAuthorized.into());
+    }
+
+    let admin = &mut ctx.accounts.admin;
+    admin.dao = dao_info.key();
+    admin.admin = ctx.accounts.user.key();
+
+    let space = 8 + std::mem::size_of::<DAO>() + MAX_NAME_LENGTH + MAX_IMAGE_LENGTH;
+    grow_account(&dao_info, &ctx.accounts.user, &ctx.accounts.system_program, space)?;
+
+    let mut data = dao_info.try_borrow_mut_data()?;
+    migrated.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+// Rewrites a vote record created before the vote side or the payer were stored into the current
+// layout. Anyone can migrate a vote record.
+pub fn migrate_vote_record(ctx: Context<MigrateVoteRecord>) -> Result<()> {
+    let record_info = ctx.accounts.user_proposal_votes.to_account_info();
+
+    if record_info.owner != ctx.program_id {
+        return Err(error::ErrorCode::InvalidVoteRecord.into());
+    }
+
+    let space = 8 + std::mem::size_of::<UserProposalVotes>();
+
+    let migrated = {
+        let data = record_info.try_borrow_data()?;
+
+        if data.len() < 8 || data[..8] != UserProposalVotes::discriminator() {
+            return Err(error::ErrorCode::InvalidVoteRecord.into());
+        }
+
+        if data.len() >= space {
+            return Err(error::ErrorCode::VoteRecordAlreadyMigrated.into());
+        }
+
+        LegacyUserProposalVotes::read(&data[8..])?.migrate(
+            ctx.accounts.voter.key(),
+            &ctx.accounts.proposal
+        )?
+    };
+
+    grow_account(&record_info, &ctx.accounts.user, &ctx.accounts.system_program, space)?;
+
+    let mut data = record_info.try_borrow_mut_data()?;
+    migrated.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+// Grows a migrated account to the current layout, the user tops up the rent
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    user: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    space: usize
+) -> Result<()> {
+    if account.data_len() >= space {
+        return Ok(());
+    }
+
+    let rent = Rent::get()?.minimum_balance(space);
+    let top_up = rent.saturating_sub(account.lamports());
+
+    if top_up > 0 {
+        let cpi_context = CpiContext::new(system_program.to_account_info(), system_program::Transfer {
+            from: user.to_account_info(),
+            to: account.clone(),
+        });
+        system_program::transfer(cpi_context, top_up)?;
+    }
+
+    account.realloc(space, false)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateProposal<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(mut)]
+    /// CHECK: The owner, discriminator and layout are checked inside the function
+    pub proposal: UncheckedAccount<'info>,
+
+    // holds the vote deposits of the migrated proposal, like the one create_proposal creates
+    #[account(
+        init,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
+        bump,
+        payer = user,
+        token::mint = token_mint,
+        token::authority = escrow_vault
+    )]
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateDao<'info> {
+    #[account(mut)]
+    /// CHECK: The owner, discriminator, layout and creator are checked inside the function
+    pub dao: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<Admin>()
+    )]
+    pub admin: Box<Account<'info, Admin>>,
+
+    // the governance mint of a legacy DAO is the mint of its treasury
+    #[account(seeds = [TREASURY_VAULT_SEED, dao.key().as_ref()], bump)]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVoteRecord<'info> {
+    #[account(
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        mut,
+        seeds = [USER_PROPOSAL_VOTES_SEED, voter.key().as_ref(), proposal.key().as_ref()],
+        bump
+    )]
+    /// CHECK: The owner, discriminator and layout are checked inside the function
+    pub user_proposal_votes: UncheckedAccount<'info>,
+
+    /// CHECK: Only used to derive the address of the vote record
+    pub voter: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
@@ -1,7 +1,21 @@
 pub mod proposal;
+pub mod proposal_instruction;
+pub mod proposal_option;
 pub mod dao;
 pub mod fee;
+pub mod admin;
+pub mod migration;
+pub mod admission;
+pub mod delegation;
+pub mod stake;
 
 pub use proposal::*;
+pub use proposal_instruction::*;
+pub use proposal_option::*;
 pub use dao::*;
 pub use fee::*;
+pub use admin::*;
+pub use migration::*;
+pub use admission::*;
+pub use delegation::*;
+pub use stake::*;
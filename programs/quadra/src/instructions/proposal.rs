@@ -1,43 +1,53 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{ transfer, Mint, Token, TokenAccount, Transfer };
+use anchor_spl::token::{ burn, transfer, Burn, Mint, Token, TokenAccount, Transfer };
 use anchor_spl::associated_token::AssociatedToken;
 use solana_program::clock::Clock;
 
 use crate::error;
+use crate::events::DaoConfigUpdated;
 use crate::state::{
+    Admin,
     DAO,
     Proposal,
+    ProposalAction,
+    ProposalStatus,
+    VoteSide,
+    ProposalConfigChange,
+    ProposalOptions,
     UserProposalVotes,
+    VoiceCredits,
     Membership,
+    CreateProposalArgs,
+    ADMIN_SEED,
     MEMBERSHIP_SEED,
     TREASURY_VAULT_SEED,
+    ESCROW_VAULT_SEED,
     USER_PROPOSAL_VOTES_SEED,
     PROPOSAL_SEED,
-    BURN_SEED,
     MAX_TITLE_LENGTH,
     MAX_DESCRIPTION_LENGTH,
+    MAX_NAME_LENGTH,
+    MAX_IMAGE_LENGTH,
+    PROPOSAL_CONFIG_CHANGE_SEED,
     BENEFICIARY_SEED,
+    PROPOSAL_VERSION,
+    PROPOSAL_OPTIONS_SEED,
+    VOICE_CREDITS_SEED,
+    quadratic_cost,
+    DAO_VERSION,
 };
 
-pub fn create_proposal(
-    ctx: Context<CreateProposal>,
-    token_amount: u64,
-    end_date: i64,
-    title: String,
-    description: String,
-    action: u8,
-    burn_on_vote: bool
-) -> Result<()> {
+pub fn create_proposal(ctx: Context<CreateProposal>, args: CreateProposalArgs) -> Result<()> {
     let dao = &mut ctx.accounts.dao;
     let proposal = &mut ctx.accounts.proposal;
     let treasury_vault = &mut ctx.accounts.treasury_vault;
     let beneficary = &ctx.accounts.beneficiary;
 
-    if end_date <= Clock::get().unwrap().unix_timestamp {
+    if args.end_date <= Clock::get().unwrap().unix_timestamp {
         return Err(error::ErrorCode::InvalidEndingSlot.into());
     }
 
-    if token_amount > treasury_vault.amount {
+    if args.token_amount > treasury_vault.amount {
         return Err(error::ErrorCode::InsufficientTreasuryBalance.into());
     }
 
@@ -45,23 +55,65 @@ pub fn create_proposal(
         return Err(error::ErrorCode::InvalidTokenMint.into());
     }
 
-    proposal.check_length(&title, &description)?;
+    ctx.accounts.membership.is_active_member(&dao.key(), ctx.accounts.user.key)?;
+
+    proposal.check_length(&args.title, &args.description)?;
+
+    // the snapshot gives wallets their voting weight so only admins can create snapshot proposals
+    if args.snapshot_root != [0; 32] {
+        if args.action == ProposalAction::Choice {
+            return Err(error::ErrorCode::InvalidProposalAction.into());
+        }
+
+        match &ctx.accounts.admin {
+            Some(admin) => admin.is_admin(&dao.key(), ctx.accounts.user.key)?,
+            None => {
+                return Err(error::ErrorCode::NotAuthorized.into());
+            }
+        }
+    }
+
+    // the config change of an update config proposal is fixed before anyone votes on it
+    match (args.action, &args.config, &mut ctx.accounts.config_change) {
+        (ProposalAction::UpdateConfig, Some(config), Some(config_change)) => {
+            // reject config changes that could never be applied
+            let mut preview = (**dao).clone();
+            preview.apply_config(config)?;
+
+            config_change.proposal = proposal.key();
+            config_change.config = config.clone();
+        }
+        (ProposalAction::UpdateConfig, _, _) | (_, Some(_), _) | (_, _, Some(_)) => {
+            return Err(error::ErrorCode::InvalidProposalAction.into());
+        }
+        _ => {}
+    }
 
     proposal.dao = dao.key();
     proposal.creator = *ctx.accounts.user.key;
     proposal.beneficiary = beneficary.key();
-    proposal.status = 0;
-    proposal.title = title;
-    proposal.description = description;
+    proposal.status = ProposalStatus::Active;
+    proposal.title = args.title;
+    proposal.description = args.description;
     proposal.yes_votes = 0;
     proposal.no_votes = 0;
-    proposal.token_amount = token_amount;
-    proposal.action = action;
-    proposal.end_date = end_date;
+    proposal.token_amount = args.token_amount;
+    proposal.action = args.action;
+    proposal.end_date = args.end_date;
     proposal.executed = false;
-    proposal.burn_on_vote = burn_on_vote;
+    proposal.burn_on_vote = args.burn_on_vote;
+    proposal.instruction_count = 0;
+    proposal.instructions_executed = 0;
+    proposal.version = PROPOSAL_VERSION;
+    proposal.executable_at = 0;
+    proposal.expires_at = 0;
+    proposal.voter_count = 0;
+    proposal.abstain_votes = 0;
+    proposal.snapshot_root = args.snapshot_root;
 
-    dao.total_proposals += 1;
+    dao.total_proposals = dao.total_proposals
+        .checked_add(1)
+        .ok_or(error::ErrorCode::MathOverflow)?;
 
     // Transfer the mint token from the user's account to the proposal's treasury vault
     let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), Transfer {
@@ -75,7 +127,7 @@ pub fn create_proposal(
     Ok(())
 }
 
-pub fn vote_on_proposal(ctx: Context<VoteOnProposal>, amount: u64, side: u8) -> Result<()> {
+pub fn vote_on_proposal(ctx: Context<VoteOnProposal>, amount: u64, side: VoteSide) -> Result<()> {
     let proposal = &mut ctx.accounts.proposal;
     let dao = &ctx.accounts.dao;
     let user_proposal_votes = &mut ctx.accounts.user_proposal_votes;
@@ -86,69 +138,290 @@ pub fn vote_on_proposal(ctx: Context<VoteOnProposal>, amount: u64, side: u8) ->
         return Err(error::ErrorCode::ProposalEnded.into());
     }
 
+    if proposal.status == ProposalStatus::Cancelled {
+        return Err(error::ErrorCode::ProposalCancelled.into());
+    }
+
     if proposal.dao != dao.key() {
         return Err(error::ErrorCode::InvalidProposal.into());
     }
 
-    // dao.is_member(user.key)?;
+    // choice proposals are voted on with vote_on_option
+    if proposal.action == ProposalAction::Choice {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
 
-    let user_previous_votes = user_proposal_votes.amount;
-    let total_votes = amount + user_previous_votes;
+    if proposal.has_snapshot() {
+        return Err(error::ErrorCode::SnapshotVoteRequired.into());
+    }
 
-    // Calculate the total cost of the votes and multiply by the decimal places of the token mint
-    let total_vote_cost = total_votes.pow(2) * (10u64).pow(token_mint.decimals as u32);
+    ctx.accounts.membership.is_active_member(&dao.key(), user.key)?;
 
-    match proposal.burn_on_vote {
-        true => {
-            // Transfer the mint token from the user's account to the proposal's treasury vault
-            let cpi_context = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_token_mint_account.to_account_info(),
-                    to: ctx.accounts.burn_vault.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                }
-            );
-            transfer(cpi_context, total_vote_cost)?;
-        }
-        false => {
-            // Transfer the mint token from the user's account to the proposal's treasury vault
-            let cpi_context = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_token_mint_account.to_account_info(),
-                    to: ctx.accounts.treasury_vault.to_account_info(),
-                    authority: ctx.accounts.user.to_account_info(),
-                }
-            );
-            transfer(cpi_context, total_vote_cost)?;
-        }
+    // a voter is locked to the side of their first vote, switching needs switch_vote_side
+    if user_proposal_votes.amount > 0 && user_proposal_votes.side != side {
+        return Err(error::ErrorCode::VoteSideMismatch.into());
     }
 
-    match side {
-        0 => {
-            proposal.no_votes += amount;
-        }
-        1 => {
-            proposal.yes_votes += amount;
-        }
-        _ => {
-            return Err(error::ErrorCode::InvalidVoteLimit.into());
-        }
+    user_proposal_votes.set_payer(user.key)?;
+
+    spend_voice_credits(
+        dao,
+        &mut ctx.accounts.voice_credits,
+        user.key(),
+        user_proposal_votes.credits_spent()?,
+        quadratic_cost(user_proposal_votes.amount, amount, 0)?
+    )?;
+
+    // Only charge the difference between the quadratic cost of the new total and what was already paid
+    let vote_cost = match dao.charges_tokens() {
+        true => user_proposal_votes.vote_cost(amount, token_mint.decimals)?,
+        false => 0,
+    };
+
+    if vote_cost > 0 {
+        pay_vote_cost(
+            proposal.burn_on_vote,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.token_mint.to_account_info(),
+            ctx.accounts.user_token_mint_account.to_account_info(),
+            ctx.accounts.escrow_vault.to_account_info(),
+            user.to_account_info(),
+            vote_cost
+        )?;
+    }
+
+    // count each wallet once towards the distinct voters
+    if user_proposal_votes.amount == 0 && amount > 0 {
+        proposal.voter_count = proposal.voter_count
+            .checked_add(1)
+            .ok_or(error::ErrorCode::MathOverflow)?;
     }
 
-    user_proposal_votes.amount += amount;
+    proposal.add_votes(side, amount)?;
+    user_proposal_votes.add_votes(side, amount, vote_cost)?;
 
     Ok(())
 }
 
-pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+// Votes on a snapshot proposal with the weight recorded for the user in the snapshot instead of
+// a membership. The weight is the budget for the quadratic cost of the votes, no tokens are moved.
+pub fn vote_with_snapshot(
+    ctx: Context<VoteWithSnapshot>,
+    weight: u64,
+    proof: Vec<[u8; 32]>,
+    amount: u64,
+    side: VoteSide
+) -> Result<()> {
     let proposal = &mut ctx.accounts.proposal;
     let dao = &ctx.accounts.dao;
-    let treasury_vault = &ctx.accounts.treasury_vault;
-    let beneficiary_account = &ctx.accounts.beneficiary;
+    let user_proposal_votes = &mut ctx.accounts.user_proposal_votes;
+    let user = &ctx.accounts.user;
+
+    if proposal.end_date <= Clock::get().unwrap().unix_timestamp {
+        return Err(error::ErrorCode::ProposalEnded.into());
+    }
+
+    if proposal.status == ProposalStatus::Cancelled {
+        return Err(error::ErrorCode::ProposalCancelled.into());
+    }
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.action == ProposalAction::Choice {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    if !proposal.has_snapshot() {
+        return Err(error::ErrorCode::NoSnapshot.into());
+    }
+
+    if !proposal.verify_snapshot_proof(user.key, weight, &proof) {
+        return Err(error::ErrorCode::InvalidSnapshotProof.into());
+    }
+
+    if user_proposal_votes.amount > 0 && user_proposal_votes.side != side {
+        return Err(error::ErrorCode::VoteSideMismatch.into());
+    }
+
+    user_proposal_votes.set_payer(user.key)?;
+
+    // the cost of all the votes of the user, not only the added ones, has to fit in the weight
+    let total_votes = user_proposal_votes.amount
+        .checked_add(amount)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+
+    if quadratic_cost(0, total_votes, ctx.accounts.token_mint.decimals)? > weight {
+        return Err(error::ErrorCode::SnapshotWeightExceeded.into());
+    }
+
+    spend_voice_credits(
+        dao,
+        &mut ctx.accounts.voice_credits,
+        user.key(),
+        user_proposal_votes.credits_spent()?,
+        quadratic_cost(user_proposal_votes.amount, amount, 0)?
+    )?;
+
+    // count each wallet once towards the distinct voters
+    if user_proposal_votes.amount == 0 && amount > 0 {
+        proposal.voter_count = proposal.voter_count
+            .checked_add(1)
+            .ok_or(error::ErrorCode::MathOverflow)?;
+    }
+
+    proposal.add_votes(side, amount)?;
+    user_proposal_votes.add_votes(side, amount, 0)?;
+
+    Ok(())
+}
+
+// Moves the user's existing votes to the other side and optionally adds `amount` new votes,
+// charging only the marginal quadratic cost of the added votes. Votes on snapshot proposals
+// can't be switched.
+pub fn switch_vote_side(
+    ctx: Context<SwitchVoteSide>,
+    side: VoteSide,
+    amount: u64
+) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    let dao = &ctx.accounts.dao;
+    let user_proposal_votes = &mut ctx.accounts.user_proposal_votes;
+    let token_mint = &ctx.accounts.token_mint;
+    let user = &ctx.accounts.user;
+
+    if proposal.end_date <= Clock::get().unwrap().unix_timestamp {
+        return Err(error::ErrorCode::ProposalEnded.into());
+    }
+
+    if proposal.status == ProposalStatus::Cancelled {
+        return Err(error::ErrorCode::ProposalCancelled.into());
+    }
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    // choice proposals are voted on with vote_on_option
+    if proposal.action == ProposalAction::Choice {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    // snapshot votes are weighted by the proof given with vote_with_snapshot and are final
+    if proposal.has_snapshot() {
+        return Err(error::ErrorCode::SnapshotVoteRequired.into());
+    }
+
+    ctx.accounts.membership.is_active_member(&dao.key(), user.key)?;
+
+    if user_proposal_votes.amount == 0 {
+        return Err(error::ErrorCode::NoVotesCast.into());
+    }
+
+    if user_proposal_votes.side == side {
+        return Err(error::ErrorCode::VoteSideMismatch.into());
+    }
+
+    // votes cast by a delegate are switched by the delegate
+    user_proposal_votes.set_payer(user.key)?;
+
+    spend_voice_credits(
+        dao,
+        &mut ctx.accounts.voice_credits,
+        user.key(),
+        user_proposal_votes.credits_spent()?,
+        quadratic_cost(user_proposal_votes.amount, amount, 0)?
+    )?;
+
+    // the votes already paid for keep their price, only the added votes are settled
+    let vote_cost = match dao.charges_tokens() {
+        true => user_proposal_votes.vote_cost(amount, token_mint.decimals)?,
+        false => 0,
+    };
+
+    let previous_votes = user_proposal_votes.amount;
+    proposal.remove_votes(user_proposal_votes.side, previous_votes)?;
+    proposal.add_votes(side, previous_votes)?;
+    proposal.add_votes(side, amount)?;
+
+    if vote_cost > 0 {
+        pay_vote_cost(
+            proposal.burn_on_vote,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.token_mint.to_account_info(),
+            ctx.accounts.user_token_mint_account.to_account_info(),
+            ctx.accounts.escrow_vault.to_account_info(),
+            user.to_account_info(),
+            vote_cost
+        )?;
+    }
+
+    user_proposal_votes.add_votes(side, amount, vote_cost)?;
+
+    Ok(())
+}
 
-    if proposal.end_date >= Clock::get().unwrap().unix_timestamp {
+// Charges the voice credits of the vote, filling in the voter's epoch credits account on first use
+pub(crate) fn spend_voice_credits(
+    dao: &Account<DAO>,
+    voice_credits: &mut Option<Box<Account<VoiceCredits>>>,
+    voter: Pubkey,
+    proposal_credits: u64,
+    credits: u64
+) -> Result<()> {
+    if let Some(voice_credits) = voice_credits {
+        voice_credits.dao = dao.key();
+        voice_credits.user = voter;
+    }
+
+    dao.spend_credits(
+        voice_credits.as_deref_mut().map(|voice_credits| &mut **voice_credits),
+        proposal_credits,
+        credits,
+        Clock::get().unwrap().unix_timestamp
+    )
+}
+
+// Burns the vote cost from the voter's token account or moves it into the proposal escrow vault
+pub(crate) fn pay_vote_cost<'info>(
+    burn_on_vote: bool,
+    token_program: AccountInfo<'info>,
+    mint: AccountInfo<'info>,
+    from: AccountInfo<'info>,
+    escrow_vault: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    amount: u64
+) -> Result<()> {
+    match burn_on_vote {
+        true => {
+            let cpi_context = CpiContext::new(token_program, Burn {
+                mint,
+                from,
+                authority,
+            });
+
+            burn(cpi_context, amount)
+        }
+        false => {
+            let cpi_context = CpiContext::new(token_program, Transfer {
+                from,
+                to: escrow_vault,
+                authority,
+            });
+
+            transfer(cpi_context, amount)
+        }
+    }
+}
+
+pub fn finalize_proposal(ctx: Context<FinalizeProposal>) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    let dao = &ctx.accounts.dao;
+
+    let now = Clock::get().unwrap().unix_timestamp;
+
+    if proposal.end_date >= now {
         return Err(error::ErrorCode::ProposalActive.into());
     }
 
@@ -156,47 +429,121 @@ pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
         return Err(error::ErrorCode::InvalidProposal.into());
     }
 
-    if proposal.status != 0 {
-        return Err(error::ErrorCode::ProposalAlreadyExecuted.into());
+    if proposal.status == ProposalStatus::Cancelled {
+        return Err(error::ErrorCode::ProposalCancelled.into());
+    }
+
+    if proposal.status != ProposalStatus::Active {
+        return Err(error::ErrorCode::ProposalAlreadyFinalized.into());
+    }
+
+    if proposal.action == ProposalAction::Choice {
+        let options = match &mut ctx.accounts.options {
+            Some(options) if options.proposal == proposal.key() => options,
+            // options are needed to vote, a choice proposal without votes fails with or without them
+            None if proposal.voter_count == 0 => {
+                proposal.status = ProposalStatus::Failed;
+
+                return Ok(());
+            }
+            _ => {
+                return Err(error::ErrorCode::InvalidProposalAction.into());
+            }
+        };
+
+        let winner = options.plurality_winner();
+
+        if winner.is_none() || !dao.quorum_reached(options.total_votes()?, proposal.voter_count) {
+            proposal.status = ProposalStatus::Failed;
+
+            return Ok(());
+        }
+
+        // choice proposals have nothing to execute
+        options.winner = winner;
+        proposal.status = ProposalStatus::Passed;
+        proposal.executed = true;
+
+        return Ok(());
     }
 
-    if proposal.yes_votes <= proposal.no_votes {
-        proposal.status = 2;
+    if
+        !dao.approved(proposal) ||
+        proposal.yes_votes < dao.min_yes_votes ||
+        !dao.quorum_reached(proposal.total_votes()?, proposal.voter_count)
+    {
+        proposal.status = ProposalStatus::Failed;
 
         return Ok(());
     }
 
-    if proposal.yes_votes < dao.min_yes_votes {
-        proposal.status = 2;
+    // a passing proposal is queued so members can react before the treasury moves
+    if dao.execution_delay > 0 {
+        proposal.status = ProposalStatus::Queued;
+        proposal.executable_at = now
+            .checked_add(dao.execution_delay)
+            .ok_or(error::ErrorCode::MathOverflow)?;
+
+        if dao.execution_window > 0 {
+            proposal.expires_at = proposal.executable_at
+                .checked_add(dao.execution_window)
+                .ok_or(error::ErrorCode::MathOverflow)?;
+        }
 
         return Ok(());
     }
 
-    if beneficiary_account.key() != proposal.beneficiary {
-        return Err(error::ErrorCode::InvalidBeneficiary.into());
+    proposal.status = ProposalStatus::Passed;
+
+    Ok(())
+}
+
+pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    let dao = &mut ctx.accounts.dao;
+    let treasury_vault = &ctx.accounts.treasury_vault;
+
+    let now = Clock::get().unwrap().unix_timestamp;
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if !proposal.check_executable(now)? {
+        proposal.status = ProposalStatus::Expired;
+
+        return Ok(());
     }
 
     match proposal.action {
-        0 => {
-            // transfer the tokens to the burn address
+        ProposalAction::Burn => {
+            // burn the tokens from the treasury
             let dao_key = dao.key();
             let bump = *ctx.bumps.get("treasury_vault").unwrap();
             let signer: &[&[&[u8]]] = &[&[TREASURY_VAULT_SEED, dao_key.as_ref(), &[bump]]];
 
             let cpi_context = CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                Transfer {
+                Burn {
+                    mint: ctx.accounts.token_mint.to_account_info(),
                     from: treasury_vault.to_account_info(),
-                    to: ctx.accounts.burn_vault.to_account_info(),
                     authority: treasury_vault.to_account_info(),
                 },
                 signer
             );
 
-            transfer(cpi_context, proposal.token_amount)?;
+            burn(cpi_context, proposal.token_amount)?;
         }
 
-        1 => {
+        ProposalAction::Transfer => {
+            // only transfers need the beneficiary account
+            let beneficiary_account = match &ctx.accounts.beneficiary {
+                Some(beneficiary) if beneficiary.key() == proposal.beneficiary => beneficiary,
+                _ => {
+                    return Err(error::ErrorCode::InvalidBeneficiary.into());
+                }
+            };
+
             // transfer the tokens to the beneficiary
             let dao_key = dao.key();
             let bump = *ctx.bumps.get("treasury_vault").unwrap();
@@ -215,20 +562,131 @@ pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
             transfer(cpi_context, proposal.token_amount)?;
         }
 
-        _ => {
-            return Err(error::ErrorCode::InvalidProposalAction.into());
+        ProposalAction::UpdateConfig => {
+            // apply the config change attached to the proposal
+            let config_change = match &ctx.accounts.config_change {
+                Some(config_change) if config_change.proposal == proposal.key() => config_change,
+                _ => {
+                    return Err(error::ErrorCode::InvalidProposalAction.into());
+                }
+            };
+
+            let old_config = dao.config();
+            dao.apply_config(&config_change.config)?;
+
+            emit!(DaoConfigUpdated {
+                dao: dao.key(),
+                old_config,
+                new_config: dao.config(),
+            });
+        }
+
+        ProposalAction::ExecuteInstructions => {
+            // the instructions are executed one by one with execute_proposal_instruction
+        }
+
+        ProposalAction::Choice => {
+            // the winning option is recorded by finalize_proposal
         }
     }
 
-    proposal.status = 1;
-    proposal.executed = true;
+    proposal.status = ProposalStatus::Passed;
+    proposal.executed = proposal.instructions_executed == proposal.instruction_count;
+
+    Ok(())
+}
+
+// Cancels a proposal, the creator can withdraw it before any votes and admins can veto it
+// until its execution starts. The creation fee stays in the treasury and escrowed votes can be
+// withdrawn with withdraw_vote_deposit.
+pub fn cancel_proposal(ctx: Context<CancelProposal>) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let proposal = &mut ctx.accounts.proposal;
+    let user = &ctx.accounts.user;
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if
+        proposal.executed ||
+        proposal.instructions_executed > 0 ||
+        !matches!(
+            proposal.status,
+            ProposalStatus::Active | ProposalStatus::Queued | ProposalStatus::Passed
+        )
+    {
+        return Err(error::ErrorCode::ProposalAlreadyExecuted.into());
+    }
+
+    match &ctx.accounts.admin {
+        Some(admin) => {
+            admin.is_admin(&dao.key(), user.key)?;
+        }
+        None => {
+            if proposal.creator != user.key() {
+                return Err(error::ErrorCode::InvalidProposalCreator.into());
+            }
+
+            if proposal.status != ProposalStatus::Active || proposal.has_votes() {
+                return Err(error::ErrorCode::ProposalAlreadyVoted.into());
+            }
+        }
+    }
+
+    proposal.status = ProposalStatus::Cancelled;
+
+    Ok(())
+}
+
+// Returns the escrowed deposit to the wallet that paid for the votes once the proposal is finalized
+// and closes the vote record
+pub fn withdraw_vote_deposit(ctx: Context<WithdrawVoteDeposit>) -> Result<()> {
+    let proposal = &ctx.accounts.proposal;
+    let dao = &ctx.accounts.dao;
+    let user_proposal_votes = &ctx.accounts.user_proposal_votes;
+    let escrow_vault = &ctx.accounts.escrow_vault;
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.status == ProposalStatus::Active {
+        return Err(error::ErrorCode::ProposalActive.into());
+    }
+
+    if user_proposal_votes.refund_recipient(ctx.accounts.voter.key) != ctx.accounts.user.key() {
+        return Err(error::ErrorCode::VotePayerMismatch.into());
+    }
+
+    // burned votes have nothing to return, the vote record is still closed to reclaim the rent
+    if !proposal.burn_on_vote && user_proposal_votes.tokens_spent > 0 {
+        let proposal_key = proposal.key();
+        let bump = *ctx.bumps.get("escrow_vault").unwrap();
+        let signer: &[&[&[u8]]] = &[&[ESCROW_VAULT_SEED, proposal_key.as_ref(), &[bump]]];
+
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: escrow_vault.to_account_info(),
+                to: ctx.accounts.user_token_mint_account.to_account_info(),
+                authority: escrow_vault.to_account_info(),
+            },
+            signer
+        );
+
+        transfer(cpi_context, user_proposal_votes.tokens_spent)?;
+    }
 
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct CreateProposal<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
     pub dao: Box<Account<'info, DAO>>,
 
     #[account(
@@ -240,6 +698,16 @@ pub struct CreateProposal<'info> {
     )]
     pub proposal: Box<Account<'info, Proposal>>,
 
+    // only created for update config proposals
+    #[account(
+        init,
+        payer = user,
+        space = 8 + std::mem::size_of::<ProposalConfigChange>() + MAX_NAME_LENGTH + MAX_IMAGE_LENGTH,
+        seeds = [PROPOSAL_CONFIG_CHANGE_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub config_change: Option<Box<Account<'info, ProposalConfigChange>>>,
+
     #[account(
         mut,
         seeds = [TREASURY_VAULT_SEED, dao.key().as_ref()],
@@ -247,6 +715,17 @@ pub struct CreateProposal<'info> {
     )]
     pub treasury_vault: Box<Account<'info, TokenAccount>>,
 
+    // holds the vote deposits until the proposal is finalized when votes are not burned
+    #[account(
+        init,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
+        bump,
+        payer = user,
+        token::mint = token_mint,
+        token::authority = escrow_vault
+    )]
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
+
     #[account(
         init_if_needed,
         payer = user,
@@ -273,9 +752,19 @@ pub struct CreateProposal<'info> {
     )]
     pub membership: Box<Account<'info, Membership>>,
 
+    // only required for snapshot proposals
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Option<Box<Account<'info, Admin>>>,
+
     #[account(mut)]
     pub user: Signer<'info>,
 
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
     pub token_mint: Account<'info, Mint>,
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -284,11 +773,85 @@ pub struct CreateProposal<'info> {
 
 #[derive(Accounts)]
 pub struct VoteOnProposal<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
     /// CHECK: It is checked inside the function
     pub dao: Box<Account<'info, DAO>>,
 
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    // stores the users vote count for the given proposal
+    #[account(
+        init_if_needed,
+        seeds = [USER_PROPOSAL_VOTES_SEED, user.key.as_ref(), proposal.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<UserProposalVotes>(),
+        payer = user
+    )]
+    pub user_proposal_votes: Box<Account<'info, UserProposalVotes>>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    // only required when the DAO uses voice credits per epoch
+    #[account(
+        init_if_needed,
+        seeds = [VOICE_CREDITS_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<VoiceCredits>(),
+        payer = user
+    )]
+    pub voice_credits: Option<Box<Account<'info, VoiceCredits>>>,
+
     #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VoteWithSnapshot<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
     /// CHECK: It is checked inside the function
     pub proposal: Box<Account<'info, Proposal>>,
 
@@ -302,19 +865,54 @@ pub struct VoteOnProposal<'info> {
     )]
     pub user_proposal_votes: Box<Account<'info, UserProposalVotes>>,
 
+    // only required when the DAO uses voice credits per epoch
     #[account(
-        mut, 
-        seeds = [TREASURY_VAULT_SEED, dao.key().as_ref()],
+        init_if_needed,
+        seeds = [VOICE_CREDITS_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<VoiceCredits>(),
+        payer = user
+    )]
+    pub voice_credits: Option<Box<Account<'info, VoiceCredits>>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SwitchVoteSide<'info> {
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        mut,
+        seeds = [USER_PROPOSAL_VOTES_SEED, user.key.as_ref(), proposal.key().as_ref()],
         bump
     )]
-    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+    pub user_proposal_votes: Box<Account<'info, UserProposalVotes>>,
 
     #[account(
-        mut, 
-        seeds = [BURN_SEED, dao.key().as_ref()],
+        mut,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
         bump
     )]
-    pub burn_vault: Box<Account<'info, TokenAccount>>,
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
 
     #[account(
         mut,
@@ -330,21 +928,65 @@ pub struct VoteOnProposal<'info> {
     )]
     pub membership: Box<Account<'info, Membership>>,
 
+    // only required when the DAO uses voice credits per epoch
+    #[account(
+        init_if_needed,
+        seeds = [VOICE_CREDITS_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<VoiceCredits>(),
+        payer = user
+    )]
+    pub voice_credits: Option<Box<Account<'info, VoiceCredits>>>,
+
     #[account(mut)]
     pub user: Signer<'info>,
+    #[account(
+        mut,
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
     pub token_mint: Account<'info, Mint>,
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct FinalizeProposal<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    // only required for choice proposals with votes
+    #[account(
+        mut,
+        seeds = [PROPOSAL_OPTIONS_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub options: Option<Box<Account<'info, ProposalOptions>>>,
+}
+
 #[derive(Accounts)]
 pub struct ExecuteProposal<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
     /// CHECK: It is checked inside the function
     pub dao: Box<Account<'info, DAO>>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
     /// CHECK: It is checked inside the function
     pub proposal: Box<Account<'info, Proposal>>,
 
@@ -355,26 +997,100 @@ pub struct ExecuteProposal<'info> {
     )]
     pub treasury_vault: Box<Account<'info, TokenAccount>>,
 
+    // only required for transfer proposals
     #[account(
         mut,
-        seeds = [BURN_SEED, dao.key().as_ref()],
-        bump,
+        token::mint = token_mint
     )]
-    pub burn_vault: Box<Account<'info, TokenAccount>>,
+    pub beneficiary: Option<Box<Account<'info, TokenAccount>>>,
 
+    // only required for update config proposals
     #[account(
-        mut,
-        associated_token::mint = token_mint,
-        associated_token::authority = beneficiary_owner
+        seeds = [PROPOSAL_CONFIG_CHANGE_SEED, proposal.key().as_ref()],
+        bump
     )]
-    pub beneficiary: Box<Account<'info, TokenAccount>>,
+    pub config_change: Option<Box<Account<'info, ProposalConfigChange>>>,
 
     #[account(mut)]
-    /// CHECK: The beneficiary would not match the beneficiary_owner if it were not correct.
-    pub beneficiary_owner: AccountInfo<'info>,
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelProposal<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    // only required when an admin vetoes the proposal
+    #[account(
+        seeds = [ADMIN_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub admin: Option<Box<Account<'info, Admin>>>,
+
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawVoteDeposit<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated)]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [USER_PROPOSAL_VOTES_SEED, voter.key.as_ref(), proposal.key().as_ref()],
+        bump
+    )]
+    pub user_proposal_votes: Box<Account<'info, UserProposalVotes>>,
+
+    /// CHECK: Only used to derive the vote record, the votes are refunded to their payer
+    /// and records without votes to the voter.
+    pub voter: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
 
     #[account(mut)]
     pub user: Signer<'info>,
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
     pub token_mint: Account<'info, Mint>,
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
@@ -0,0 +1,211 @@
+use anchor_lang::prelude::*;
+use solana_program::instruction::{ AccountMeta, Instruction };
+use solana_program::program::invoke_signed;
+use anchor_spl::token::TokenAccount;
+
+use crate::error;
+use crate::state::{
+    DAO,
+    Proposal,
+    ProposalAction,
+    ProposalStatus,
+    ProposalInstruction,
+    InstructionAccount,
+    TREASURY_VAULT_SEED,
+    DAO_AUTHORITY_SEED,
+    PROPOSAL_INSTRUCTION_SEED,
+    MAX_PROPOSAL_INSTRUCTIONS,
+    PROPOSAL_VERSION,
+    DAO_VERSION,
+};
+
+// Appends an instruction to an execute instructions proposal, only possible before any votes
+pub fn insert_proposal_instruction(
+    ctx: Context<InsertProposalInstruction>,
+    program_id: Pubkey,
+    accounts: Vec<InstructionAccount>,
+    data: Vec<u8>
+) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let proposal = &mut ctx.accounts.proposal;
+    let proposal_instruction = &mut ctx.accounts.proposal_instruction;
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.creator != ctx.accounts.user.key() {
+        return Err(error::ErrorCode::InvalidProposalCreator.into());
+    }
+
+    if proposal.action != ProposalAction::ExecuteInstructions {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    if proposal.status != ProposalStatus::Active || proposal.has_votes() {
+        return Err(error::ErrorCode::ProposalAlreadyVoted.into());
+    }
+
+    if proposal.instruction_count >= MAX_PROPOSAL_INSTRUCTIONS {
+        return Err(error::ErrorCode::TooManyInstructions.into());
+    }
+
+    proposal_instruction.proposal = proposal.key();
+    proposal_instruction.index = proposal.instruction_count;
+    proposal_instruction.program_id = program_id;
+    proposal_instruction.accounts = accounts;
+    proposal_instruction.data = data;
+    proposal_instruction.executed = false;
+
+    proposal.instruction_count = proposal.instruction_count
+        .checked_add(1)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+
+    Ok(())
+}
+
+// Replays the next instruction of a passed proposal with the DAO authority and the treasury vault
+// as signers, the accounts of the instruction and its program are passed as remaining accounts.
+// Queued proposals can only be executed inside their execution window.
+pub fn execute_proposal_instruction<'info>(
+    ctx: Context<'_, '_, '_, 'info, ExecuteProposalInstruction<'info>>
+) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let proposal = &mut ctx.accounts.proposal;
+    let proposal_instruction = &mut ctx.accounts.proposal_instruction;
+    let treasury_vault = &ctx.accounts.treasury_vault;
+    let dao_authority = &ctx.accounts.dao_authority;
+
+    let now = Clock::get().unwrap().unix_timestamp;
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.action != ProposalAction::ExecuteInstructions {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    if !proposal.check_executable(now)? {
+        proposal.status = ProposalStatus::Expired;
+
+        return Ok(());
+    }
+
+    if proposal_instruction.proposal != proposal.key() || proposal_instruction.executed {
+        return Err(error::ErrorCode::ProposalAlreadyExecuted.into());
+    }
+
+    if proposal_instruction.index != proposal.instructions_executed {
+        return Err(error::ErrorCode::InstructionOutOfOrder.into());
+    }
+
+    let instruction = Instruction {
+        program_id: proposal_instruction.program_id,
+        accounts: proposal_instruction.accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: proposal_instruction.data.clone(),
+    };
+
+    let mut account_infos = ctx.remaining_accounts.to_vec();
+    account_infos.push(treasury_vault.to_account_info());
+    account_infos.push(dao_authority.to_account_info());
+
+    let dao_key = dao.key();
+    let treasury_bump = *ctx.bumps.get("treasury_vault").unwrap();
+    let authority_bump = *ctx.bumps.get("dao_authority").unwrap();
+    let signer: &[&[&[u8]]] = &[
+        &[TREASURY_VAULT_SEED, dao_key.as_ref(), &[treasury_bump]],
+        &[DAO_AUTHORITY_SEED, dao_key.as_ref(), &[authority_bump]],
+    ];
+
+    invoke_signed(&instruction, &account_infos, signer)?;
+
+    proposal_instruction.executed = true;
+    proposal.instructions_executed = proposal.instructions_executed
+        .checked_add(1)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+    proposal.status = ProposalStatus::Passed;
+    proposal.executed = proposal.instructions_executed == proposal.instruction_count;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(program_id: Pubkey, accounts: Vec<InstructionAccount>, data: Vec<u8>)]
+pub struct InsertProposalInstruction<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 +
+        std::mem::size_of::<ProposalInstruction>() +
+        accounts.len() * std::mem::size_of::<InstructionAccount>() +
+        data.len(),
+        seeds = [PROPOSAL_INSTRUCTION_SEED, proposal.key().as_ref(), &[proposal.instruction_count]],
+        bump
+    )]
+    pub proposal_instruction: Box<Account<'info, ProposalInstruction>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposalInstruction<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        mut,
+        seeds = [PROPOSAL_INSTRUCTION_SEED, proposal.key().as_ref(), &[proposal_instruction.index]],
+        bump
+    )]
+    pub proposal_instruction: Box<Account<'info, ProposalInstruction>>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_VAULT_SEED, dao.key().as_ref()],
+        bump
+    )]
+    pub treasury_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [DAO_AUTHORITY_SEED, dao.key().as_ref()],
+        bump
+    )]
+    /// CHECK: PDA without data that signs for the DAO, it can hold lamports and authorities
+    pub dao_authority: UncheckedAccount<'info>,
+
+    pub user: Signer<'info>,
+}
@@ -0,0 +1,312 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{ transfer, Mint, Token, TokenAccount, Transfer };
+use anchor_spl::associated_token::AssociatedToken;
+use solana_program::clock::Clock;
+
+use crate::error;
+use crate::instructions::proposal::{ pay_vote_cost, spend_voice_credits };
+use crate::state::{
+    DAO,
+    Proposal,
+    ProposalAction,
+    ProposalStatus,
+    ProposalOptions,
+    UserOptionVotes,
+    VoiceCredits,
+    Membership,
+    MEMBERSHIP_SEED,
+    ESCROW_VAULT_SEED,
+    PROPOSAL_OPTIONS_SEED,
+    USER_OPTION_VOTES_SEED,
+    MAX_PROPOSAL_OPTIONS,
+    MAX_OPTION_LABEL_LENGTH,
+    PROPOSAL_VERSION,
+    VOICE_CREDITS_SEED,
+    DAO_VERSION,
+};
+
+// Sets the options of a choice proposal, only possible before any votes
+pub fn set_proposal_options(ctx: Context<SetProposalOptions>, labels: Vec<String>) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let proposal = &ctx.accounts.proposal;
+    let options = &mut ctx.accounts.options;
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.creator != ctx.accounts.user.key() {
+        return Err(error::ErrorCode::InvalidProposalCreator.into());
+    }
+
+    if proposal.action != ProposalAction::Choice {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    if proposal.status != ProposalStatus::Active || proposal.voter_count > 0 {
+        return Err(error::ErrorCode::ProposalAlreadyVoted.into());
+    }
+
+    ProposalOptions::check_labels(&labels)?;
+
+    options.proposal = proposal.key();
+    options.tallies = vec![0; labels.len()];
+    options.labels = labels;
+    options.winner = None;
+
+    Ok(())
+}
+
+// Adds `amount` votes to one option of a choice proposal. Each option is priced on its own so the
+// user pays the sum of the squared votes per option.
+pub fn vote_on_option(ctx: Context<VoteOnOption>, option: u8, amount: u64) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    let dao = &ctx.accounts.dao;
+    let options = &mut ctx.accounts.options;
+    let user_option_votes = &mut ctx.accounts.user_option_votes;
+    let user = &ctx.accounts.user;
+
+    if proposal.end_date <= Clock::get().unwrap().unix_timestamp {
+        return Err(error::ErrorCode::ProposalEnded.into());
+    }
+
+    if proposal.status == ProposalStatus::Cancelled {
+        return Err(error::ErrorCode::ProposalCancelled.into());
+    }
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.action != ProposalAction::Choice {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    ctx.accounts.membership.is_active_member(&dao.key(), user.key)?;
+
+    // a new vote record starts with no votes on every option
+    if user_option_votes.votes.is_empty() {
+        user_option_votes.votes = vec![0; options.tallies.len()];
+    }
+
+    spend_voice_credits(
+        dao,
+        &mut ctx.accounts.voice_credits,
+        user.key(),
+        user_option_votes.credits_spent()?,
+        user_option_votes.vote_cost(option, amount, 0)?
+    )?;
+
+    let vote_cost = match dao.charges_tokens() {
+        true => user_option_votes.vote_cost(option, amount, ctx.accounts.token_mint.decimals)?,
+        false => 0,
+    };
+
+    if vote_cost > 0 {
+        pay_vote_cost(
+            proposal.burn_on_vote,
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.token_mint.to_account_info(),
+            ctx.accounts.user_token_mint_account.to_account_info(),
+            ctx.accounts.escrow_vault.to_account_info(),
+            user.to_account_info(),
+            vote_cost
+        )?;
+    }
+
+    // count each wallet once towards the distinct voters
+    if !user_option_votes.has_voted() && amount > 0 {
+        proposal.voter_count = proposal.voter_count
+            .checked_add(1)
+            .ok_or(error::ErrorCode::MathOverflow)?;
+    }
+
+    options.add_votes(option, amount)?;
+    user_option_votes.add_votes(option, amount, vote_cost)?;
+
+    Ok(())
+}
+
+// Returns the voter's escrowed deposit once the choice proposal is finalized and closes their vote record
+pub fn withdraw_option_vote_deposit(ctx: Context<WithdrawOptionVoteDeposit>) -> Result<()> {
+    let proposal = &ctx.accounts.proposal;
+    let dao = &ctx.accounts.dao;
+    let user_option_votes = &ctx.accounts.user_option_votes;
+    let escrow_vault = &ctx.accounts.escrow_vault;
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.status == ProposalStatus::Active {
+        return Err(error::ErrorCode::ProposalActive.into());
+    }
+
+    // burned votes have nothing to return, the vote record is still closed to reclaim the rent
+    if !proposal.burn_on_vote && user_option_votes.tokens_spent > 0 {
+        let proposal_key = proposal.key();
+        let bump = *ctx.bumps.get("escrow_vault").unwrap();
+        let signer: &[&[&[u8]]] = &[&[ESCROW_VAULT_SEED, proposal_key.as_ref(), &[bump]]];
+
+        let cpi_context = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            Transfer {
+                from: escrow_vault.to_account_info(),
+                to: ctx.accounts.user_token_mint_account.to_account_info(),
+                authority: escrow_vault.to_account_info(),
+            },
+            signer
+        );
+
+        transfer(cpi_context, user_option_votes.tokens_spent)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetProposalOptions<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated)]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        init,
+        payer = user,
+        space = 8 +
+        std::mem::size_of::<ProposalOptions>() +
+        MAX_PROPOSAL_OPTIONS * (4 + MAX_OPTION_LABEL_LENGTH + 8),
+        seeds = [PROPOSAL_OPTIONS_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub options: Box<Account<'info, ProposalOptions>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VoteOnOption<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        mut,
+        seeds = [PROPOSAL_OPTIONS_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub options: Box<Account<'info, ProposalOptions>>,
+
+    // stores the users votes per option for the given proposal
+    #[account(
+        init_if_needed,
+        seeds = [USER_OPTION_VOTES_SEED, user.key.as_ref(), proposal.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<UserOptionVotes>() + MAX_PROPOSAL_OPTIONS * 8,
+        payer = user
+    )]
+    pub user_option_votes: Box<Account<'info, UserOptionVotes>>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    // only required when the DAO uses voice credits per epoch
+    #[account(
+        init_if_needed,
+        seeds = [VOICE_CREDITS_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<VoiceCredits>(),
+        payer = user
+    )]
+    pub voice_credits: Option<Box<Account<'info, VoiceCredits>>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        mut,
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawOptionVoteDeposit<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated)]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [USER_OPTION_VOTES_SEED, user.key.as_ref(), proposal.key().as_ref()],
+        bump
+    )]
+    pub user_option_votes: Box<Account<'info, UserOptionVotes>>,
+
+    #[account(
+        mut,
+        seeds = [ESCROW_VAULT_SEED, proposal.key().as_ref()],
+        bump
+    )]
+    pub escrow_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
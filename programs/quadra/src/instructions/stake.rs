@@ -0,0 +1,331 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{ transfer, Mint, Token, TokenAccount, Transfer };
+use anchor_spl::associated_token::AssociatedToken;
+use solana_program::clock::Clock;
+
+use crate::error;
+use crate::instructions::proposal::spend_voice_credits;
+use crate::state::{
+    DAO,
+    Membership,
+    Proposal,
+    ProposalAction,
+    ProposalStatus,
+    StakeAccount,
+    UserProposalVotes,
+    VoiceCredits,
+    VoteSide,
+    MEMBERSHIP_SEED,
+    STAKE_SEED,
+    STAKE_VAULT_SEED,
+    USER_PROPOSAL_VOTES_SEED,
+    VOICE_CREDITS_SEED,
+    MAX_LOCKUP_PERIOD,
+    PROPOSAL_VERSION,
+    quadratic_cost,
+    DAO_VERSION,
+};
+
+// Moves governance tokens into the stake vault of the member. Staking again adds to the stake,
+// the lockup can only be extended.
+pub fn stake(ctx: Context<Stake>, amount: u64, lockup_period: i64) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let stake_account = &mut ctx.accounts.stake_account;
+    let user = &ctx.accounts.user;
+
+    ctx.accounts.membership.is_active_member(&dao.key(), user.key)?;
+
+    if amount == 0 {
+        return Err(error::ErrorCode::NoTokens.into());
+    }
+
+    if !(0..=MAX_LOCKUP_PERIOD).contains(&lockup_period) {
+        return Err(error::ErrorCode::InvalidLockUp.into());
+    }
+
+    let lockup_end = Clock::get()?.unix_timestamp
+        .checked_add(lockup_period)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+
+    stake_account.dao = dao.key();
+    stake_account.user = user.key();
+    stake_account.amount = stake_account.amount
+        .checked_add(amount)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+    stake_account.lockup_end = stake_account.lockup_end.max(lockup_end);
+
+    let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), Transfer {
+        from: ctx.accounts.user_token_mint_account.to_account_info(),
+        to: ctx.accounts.stake_vault.to_account_info(),
+        authority: user.to_account_info(),
+    });
+    transfer(cpi_context, amount)?;
+
+    Ok(())
+}
+
+// Returns staked tokens to the member once the lockup has ended
+pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<()> {
+    let dao = &ctx.accounts.dao;
+    let stake_account = &mut ctx.accounts.stake_account;
+    let stake_vault = &ctx.accounts.stake_vault;
+    let user = &ctx.accounts.user;
+
+    if stake_account.amount == 0 {
+        return Err(error::ErrorCode::NotStaked.into());
+    }
+
+    if Clock::get()?.unix_timestamp < stake_account.lockup_end {
+        return Err(error::ErrorCode::LockupPeriodNotEnded.into());
+    }
+
+    if amount == 0 {
+        return Err(error::ErrorCode::InsufficientStake.into());
+    }
+
+    stake_account.amount = stake_account.amount
+        .checked_sub(amount)
+        .ok_or(error::ErrorCode::InsufficientStake)?;
+
+    let dao_key = dao.key();
+    let user_key = user.key();
+    let bump = *ctx.bumps.get("stake_vault").unwrap();
+    let signer: &[&[&[u8]]] = &[&[STAKE_VAULT_SEED, dao_key.as_ref(), user_key.as_ref(), &[bump]]];
+
+    let cpi_context = CpiContext::new_with_signer(
+        ctx.accounts.token_program.to_account_info(),
+        Transfer {
+            from: stake_vault.to_account_info(),
+            to: ctx.accounts.user_token_mint_account.to_account_info(),
+            authority: stake_vault.to_account_info(),
+        },
+        signer
+    );
+    transfer(cpi_context, amount)?;
+
+    Ok(())
+}
+
+// Votes with the weight of the staked tokens instead of paying for the votes. The quadratic cost
+// of all the votes of the member on the proposal has to fit in the weight, and the stake has to
+// stay locked until the proposal ends so it cannot be moved to another wallet and reused.
+pub fn vote_with_stake(ctx: Context<VoteWithStake>, amount: u64, side: VoteSide) -> Result<()> {
+    let proposal = &mut ctx.accounts.proposal;
+    let dao = &ctx.accounts.dao;
+    let stake_account = &ctx.accounts.stake_account;
+    let user_proposal_votes = &mut ctx.accounts.user_proposal_votes;
+    let user = &ctx.accounts.user;
+
+    let now = Clock::get()?.unix_timestamp;
+
+    if proposal.end_date <= now {
+        return Err(error::ErrorCode::ProposalEnded.into());
+    }
+
+    if proposal.status == ProposalStatus::Cancelled {
+        return Err(error::ErrorCode::ProposalCancelled.into());
+    }
+
+    if proposal.dao != dao.key() {
+        return Err(error::ErrorCode::InvalidProposal.into());
+    }
+
+    if proposal.action == ProposalAction::Choice {
+        return Err(error::ErrorCode::InvalidProposalAction.into());
+    }
+
+    if proposal.has_snapshot() {
+        return Err(error::ErrorCode::SnapshotVoteRequired.into());
+    }
+
+    ctx.accounts.membership.is_active_member(&dao.key(), user.key)?;
+
+    if stake_account.amount == 0 {
+        return Err(error::ErrorCode::NotStaked.into());
+    }
+
+    if stake_account.lockup_end < proposal.end_date {
+        return Err(error::ErrorCode::InvalidLockUp.into());
+    }
+
+    if user_proposal_votes.amount > 0 && user_proposal_votes.side != side {
+        return Err(error::ErrorCode::VoteSideMismatch.into());
+    }
+
+    user_proposal_votes.set_payer(user.key)?;
+
+    let total_votes = user_proposal_votes.amount
+        .checked_add(amount)
+        .ok_or(error::ErrorCode::MathOverflow)?;
+
+    let weight = stake_account.voting_weight(dao.lockup_boost_bps, now)?;
+
+    if quadratic_cost(0, total_votes, ctx.accounts.token_mint.decimals)? > weight {
+        return Err(error::ErrorCode::InsufficientStake.into());
+    }
+
+    spend_voice_credits(
+        dao,
+        &mut ctx.accounts.voice_credits,
+        user.key(),
+        user_proposal_votes.credits_spent()?,
+        quadratic_cost(user_proposal_votes.amount, amount, 0)?
+    )?;
+
+    // count each wallet once towards the distinct voters
+    if user_proposal_votes.amount == 0 && amount > 0 {
+        proposal.voter_count = proposal.voter_count
+            .checked_add(1)
+            .ok_or(error::ErrorCode::MathOverflow)?;
+    }
+
+    proposal.add_votes(side, amount)?;
+    user_proposal_votes.add_votes(side, amount, 0)?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [STAKE_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<StakeAccount>()
+    )]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [STAKE_VAULT_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = stake_vault
+    )]
+    pub stake_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    #[account(
+        mut,
+        seeds = [STAKE_VAULT_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub stake_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = user
+    )]
+    pub user_token_mint_account: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct VoteWithStake<'info> {
+    #[account(
+        constraint = dao.version == DAO_VERSION @ error::ErrorCode::DaoNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub dao: Box<Account<'info, DAO>>,
+
+    #[account(
+        mut,
+        constraint = proposal.version == PROPOSAL_VERSION @ error::ErrorCode::ProposalNotMigrated
+    )]
+    /// CHECK: It is checked inside the function
+    pub proposal: Box<Account<'info, Proposal>>,
+
+    #[account(
+        seeds = [STAKE_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub stake_account: Box<Account<'info, StakeAccount>>,
+
+    // stores the users vote count for the given proposal
+    #[account(
+        init_if_needed,
+        seeds = [USER_PROPOSAL_VOTES_SEED, user.key.as_ref(), proposal.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<UserProposalVotes>(),
+        payer = user
+    )]
+    pub user_proposal_votes: Box<Account<'info, UserProposalVotes>>,
+
+    #[account(
+        seeds = [MEMBERSHIP_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub membership: Box<Account<'info, Membership>>,
+
+    // only required when the DAO uses voice credits per epoch
+    #[account(
+        init_if_needed,
+        seeds = [VOICE_CREDITS_SEED, dao.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + std::mem::size_of::<VoiceCredits>(),
+        payer = user
+    )]
+    pub voice_credits: Option<Box<Account<'info, VoiceCredits>>>,
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+    #[account(
+        address = dao.governance_mint @ error::ErrorCode::InvalidTokenMint
+    )]
+    pub token_mint: Account<'info, Mint>,
+    pub system_program: Program<'info, System>,
+}
@@ -1,9 +1,11 @@
 pub mod state;
 pub mod error;
+pub mod events;
 pub mod instructions;
 
 use anchor_lang::prelude::*;
 use instructions::*;
+use state::{ CreateProposalArgs, DaoConfigArgs, InstructionAccount, VoteSide };
 
 declare_id!("25Kw1yUstwo9dBugYc3GNY1cniMMwZjatXQWuBfLV2Da");
 
@@ -21,7 +23,8 @@ pub mod quadra {
         image: String,
         min_yes_votes: u64,
         proposal_creation_fee: u64,
-        membership_fee: u64
+        membership_fee: u64,
+        leave_refund_bps: u16
     ) -> Result<()> {
         instructions::create_dao(
             ctx,
@@ -29,39 +32,157 @@ pub mod quadra {
             image,
             min_yes_votes,
             proposal_creation_fee,
-            membership_fee
+            membership_fee,
+            leave_refund_bps
         )
     }
 
-    pub fn join_dao(ctx: Context<JoinDAO>) -> Result<()> {
-        instructions::join_dao(ctx)
+    pub fn update_dao_config(ctx: Context<UpdateDAOConfig>, config: DaoConfigArgs) -> Result<()> {
+        instructions::update_dao_config(ctx, config)
     }
 
-    pub fn create_proposal(
-        ctx: Context<CreateProposal>,
-        token_amount: u64,
-        end_date: i64,
-        title: String,
-        description: String,
-        action: u8,
-        burn_on_vote: bool
-    ) -> Result<()> {
-        instructions::create_proposal(
-            ctx,
-            token_amount,
-            end_date,
-            title,
-            description,
-            action,
-            burn_on_vote
-        )
+    pub fn add_admin(ctx: Context<AddAdmin>) -> Result<()> {
+        instructions::add_admin(ctx)
+    }
+
+    pub fn remove_admin(ctx: Context<RemoveAdmin>) -> Result<()> {
+        instructions::remove_admin(ctx)
+    }
+
+    pub fn join_dao(ctx: Context<JoinDAO>, proof: Vec<[u8; 32]>) -> Result<()> {
+        instructions::join_dao(ctx, proof)
+    }
+
+    pub fn add_admission_entry(ctx: Context<AddAdmissionEntry>) -> Result<()> {
+        instructions::add_admission_entry(ctx)
+    }
+
+    pub fn remove_admission_entry(ctx: Context<RemoveAdmissionEntry>) -> Result<()> {
+        instructions::remove_admission_entry(ctx)
+    }
+
+    pub fn leave_dao(ctx: Context<LeaveDAO>) -> Result<()> {
+        instructions::leave_dao(ctx)
+    }
+
+    pub fn expel_member(ctx: Context<ModerateMember>) -> Result<()> {
+        instructions::expel_member(ctx)
+    }
+
+    pub fn reactivate_member(ctx: Context<ModerateMember>) -> Result<()> {
+        instructions::reactivate_member(ctx)
+    }
+
+    pub fn flush_burn_vault(ctx: Context<FlushBurnVault>) -> Result<()> {
+        instructions::flush_burn_vault(ctx)
+    }
+
+    pub fn create_proposal(ctx: Context<CreateProposal>, args: CreateProposalArgs) -> Result<()> {
+        instructions::create_proposal(ctx, args)
     }
 
-    pub fn vote_on_proposal(ctx: Context<VoteOnProposal>, amount: u64, side: u8) -> Result<()> {
+    pub fn vote_on_proposal(
+        ctx: Context<VoteOnProposal>,
+        amount: u64,
+        side: VoteSide
+    ) -> Result<()> {
         instructions::vote_on_proposal(ctx, amount, side)
     }
 
+    pub fn vote_with_snapshot(
+        ctx: Context<VoteWithSnapshot>,
+        weight: u64,
+        proof: Vec<[u8; 32]>,
+        amount: u64,
+        side: VoteSide
+    ) -> Result<()> {
+        instructions::vote_with_snapshot(ctx, weight, proof, amount, side)
+    }
+
+    pub fn delegate(ctx: Context<Delegate>, delegate: Pubkey) -> Result<()> {
+        instructions::delegate(ctx, delegate)
+    }
+
+    pub fn undelegate(ctx: Context<Undelegate>) -> Result<()> {
+        instructions::undelegate(ctx)
+    }
+
+    pub fn vote_as_delegate(ctx: Context<VoteAsDelegate>, amount: u64, side: VoteSide) -> Result<()> {
+        instructions::vote_as_delegate(ctx, amount, side)
+    }
+
+    pub fn stake(ctx: Context<Stake>, amount: u64, lockup_period: i64) -> Result<()> {
+        instructions::stake(ctx, amount, lockup_period)
+    }
+
+    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<()> {
+        instructions::unstake(ctx, amount)
+    }
+
+    pub fn vote_with_stake(ctx: Context<VoteWithStake>, amount: u64, side: VoteSide) -> Result<()> {
+        instructions::vote_with_stake(ctx, amount, side)
+    }
+
+    pub fn switch_vote_side(
+        ctx: Context<SwitchVoteSide>,
+        side: VoteSide,
+        amount: u64
+    ) -> Result<()> {
+        instructions::switch_vote_side(ctx, side, amount)
+    }
+
+    pub fn finalize_proposal(ctx: Context<FinalizeProposal>) -> Result<()> {
+        instructions::finalize_proposal(ctx)
+    }
+
     pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
         instructions::execute_proposal(ctx)
     }
+
+    pub fn set_proposal_options(ctx: Context<SetProposalOptions>, labels: Vec<String>) -> Result<()> {
+        instructions::set_proposal_options(ctx, labels)
+    }
+
+    pub fn vote_on_option(ctx: Context<VoteOnOption>, option: u8, amount: u64) -> Result<()> {
+        instructions::vote_on_option(ctx, option, amount)
+    }
+
+    pub fn withdraw_option_vote_deposit(ctx: Context<WithdrawOptionVoteDeposit>) -> Result<()> {
+        instructions::withdraw_option_vote_deposit(ctx)
+    }
+
+    pub fn insert_proposal_instruction(
+        ctx: Context<InsertProposalInstruction>,
+        program_id: Pubkey,
+        accounts: Vec<InstructionAccount>,
+        data: Vec<u8>
+    ) -> Result<()> {
+        instructions::insert_proposal_instruction(ctx, program_id, accounts, data)
+    }
+
+    pub fn execute_proposal_instruction<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteProposalInstruction<'info>>
+    ) -> Result<()> {
+        instructions::execute_proposal_instruction(ctx)
+    }
+
+    pub fn cancel_proposal(ctx: Context<CancelProposal>) -> Result<()> {
+        instructions::cancel_proposal(ctx)
+    }
+
+    pub fn migrate_proposal(ctx: Context<MigrateProposal>) -> Result<()> {
+        instructions::migrate_proposal(ctx)
+    }
+
+    pub fn migrate_dao(ctx: Context<MigrateDao>, member_count: u64) -> Result<()> {
+        instructions::migrate_dao(ctx, member_count)
+    }
+
+    pub fn migrate_vote_record(ctx: Context<MigrateVoteRecord>) -> Result<()> {
+        instructions::migrate_vote_record(ctx)
+    }
+
+    pub fn withdraw_vote_deposit(ctx: Context<WithdrawVoteDeposit>) -> Result<()> {
+        instructions::withdraw_vote_deposit(ctx)
+    }
 }
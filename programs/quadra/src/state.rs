@@ -1,5 +1,5 @@
 use anchor_lang::prelude::*;
-use solana_program::{ pubkey, pubkey::Pubkey };
+use solana_program::{ keccak, pubkey, pubkey::Pubkey };
 
 use crate::error;
 
@@ -10,6 +10,7 @@ pub const FEE_SEED: &[u8] = b"fee";
 pub const ADMIN_SEED: &[u8] = b"admin";
 pub const MEMBERSHIP_SEED: &[u8] = b"membership";
 pub const TREASURY_VAULT_SEED: &[u8] = b"treasury_vault";
+pub const ESCROW_VAULT_SEED: &[u8] = b"escrow_vault";
 pub const BURN_SEED: &[u8] = b"burn";
 pub const USDC_VAULT_SEED: &[u8] = b"usdc_vault";
 pub const STABLE_VAULT_SEED: &[u8] = b"stable_vault";
@@ -25,10 +26,38 @@ pub const MAX_NAME_LENGTH: usize = 50;
 pub const MAX_IMAGE_LENGTH: usize = 500;
 pub const MAX_BIO_LENGTH: usize = 500;
 
+pub const MIN_ADMINS: u8 = 1;
+pub const MAX_ADMINS: u8 = 5;
+
 pub const DAO_SEED: &[u8] = b"dao";
+pub const DAO_VERSION: u8 = 1;
+pub const DAO_AUTHORITY_SEED: &[u8] = b"dao_authority"; // signs the instructions of passed proposals
+pub const CREATOR_REGISTRY_SEED: &[u8] = b"creator_registry";
+pub const MAX_DAOS_PER_CREATOR: usize = 20;
 
 pub const PROPOSAL_SEED: &[u8] = b"proposal";
 pub const USER_PROPOSAL_VOTES_SEED: &[u8] = b"user_proposal_votes";
+pub const PROPOSAL_CONFIG_CHANGE_SEED: &[u8] = b"proposal_config_change";
+pub const PROPOSAL_INSTRUCTION_SEED: &[u8] = b"proposal_instruction";
+pub const MAX_PROPOSAL_INSTRUCTIONS: u8 = 10;
+pub const PROPOSAL_VERSION: u8 = 1;
+pub const PROPOSAL_OPTIONS_SEED: &[u8] = b"proposal_options";
+pub const USER_OPTION_VOTES_SEED: &[u8] = b"user_option_votes";
+pub const MIN_PROPOSAL_OPTIONS: usize = 2;
+pub const MAX_PROPOSAL_OPTIONS: usize = 10;
+pub const MAX_OPTION_LABEL_LENGTH: usize = 50;
+pub const VOICE_CREDITS_SEED: &[u8] = b"voice_credits";
+pub const ADMISSION_SEED: &[u8] = b"admission";
+pub const ADMISSION_CLAIM_SEED: &[u8] = b"admission_claim";
+pub const METADATA_SEED: &[u8] = b"metadata";
+pub const METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+pub const DELEGATION_SEED: &[u8] = b"delegation";
+pub const STAKE_SEED: &[u8] = b"stake";
+pub const STAKE_VAULT_SEED: &[u8] = b"stake_vault";
+pub const MAX_LOCKUP_PERIOD: i64 = 4 * 365 * 24 * 60 * 60; // lockups this long get the full boost
+
+pub const MAX_BASIS_POINTS: u16 = 10_000;
+pub const MAX_PROPOSAL_ACTIONS: usize = 8; // slots for per action approval thresholds
 
 pub const POOL_INFO_SEED: &[u8] = b"pool_info";
 pub const DEPOSIT_FEE: f64 = 0.01;
@@ -49,6 +78,116 @@ pub struct DAO {
     pub min_yes_votes: u64, // minimum yes votes required for a proposal to pass
     pub proposal_creation_fee: u64,
     pub membership_fee: u64,
+    pub leave_refund_bps: u16, // share of the membership fee refunded when a member leaves
+    pub admin_count: u8,
+    pub execution_delay: i64, // seconds a passed proposal stays queued before it can be executed
+    pub execution_window: i64, // seconds a queued proposal stays executable, 0 = no expiry
+    pub quorum_mode: QuorumMode,
+    pub quorum: u64, // votes, voters or basis points of active members depending on quorum_mode
+    pub member_count: u64, // active members
+    pub approval_threshold_bps: u16, // share of yes + no votes that must be yes, 0 = simple majority
+    pub action_threshold_bps: [u16; MAX_PROPOSAL_ACTIONS], // indexed by ProposalAction, 0 = DAO threshold
+    pub large_transfer_amount: u64, // transfers above this amount use large_transfer_threshold_bps, 0 = disabled
+    pub large_transfer_threshold_bps: u16,
+    pub credit_mode: CreditMode,
+    pub credit_allowance: u64, // voice credits per proposal or per epoch depending on credit_mode
+    pub credit_epoch_length: i64, // seconds, only used by CreditMode::PerEpoch
+    pub credit_charge_tokens: bool, // votes paid with credits also cost tokens
+    pub admission_policy: AdmissionPolicy,
+    pub admission_root: [u8; 32], // merkle root of the allowed wallets
+    pub admission_key: Pubkey, // required NFT collection or attestation issuer depending on admission_policy
+    pub lockup_boost_bps: u16, // extra stake weight for a MAX_LOCKUP_PERIOD lockup, 0 = no boost
+    pub governance_mint: Pubkey, // mint of the treasury, votes, stakes and fees
+    pub version: u8, // DAO_VERSION once created or migrated into the current layout
+}
+
+// DAO layout written before the governance settings were added, migrated with migrate_dao
+#[derive(AnchorDeserialize)]
+pub struct LegacyDao {
+    pub creator: Pubkey,
+    pub name: String,
+    pub image: String,
+    pub treasury_vault: Pubkey,
+    pub burn_vault: Pubkey,
+    pub total_proposals: u64,
+    pub min_yes_votes: u64,
+    pub proposal_creation_fee: u64,
+    pub membership_fee: u64,
+}
+
+// Vote record layouts written before the payer was stored, told apart by the size of the record
+pub enum LegacyUserProposalVotes {
+    // the first layout, votes were paid into the treasury in full and the side was not recorded
+    AmountOnly {
+        amount: u64,
+    },
+    // votes deposited into the escrow and locked to one side
+    Sided {
+        amount: u64,
+        tokens_spent: u64,
+        side: VoteSide,
+    },
+}
+
+// Lists the DAOs created by a wallet, in creation order
+#[account]
+pub struct CreatorRegistry {
+    pub creator: Pubkey,
+    pub daos: Vec<Pubkey>,
+}
+
+// Configurable DAO settings, a None value leaves the current setting untouched
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct DaoConfigArgs {
+    pub name: Option<String>,
+    pub image: Option<String>,
+    pub min_yes_votes: Option<u64>,
+    pub proposal_creation_fee: Option<u64>,
+    pub membership_fee: Option<u64>,
+    pub leave_refund_bps: Option<u16>,
+    pub execution_delay: Option<i64>,
+    pub execution_window: Option<i64>,
+    pub quorum_mode: Option<QuorumMode>,
+    pub quorum: Option<u64>,
+    pub approval_threshold_bps: Option<u16>,
+    pub action_threshold_bps: Option<[u16; MAX_PROPOSAL_ACTIONS]>,
+    pub large_transfer_amount: Option<u64>,
+    pub large_transfer_threshold_bps: Option<u16>,
+    pub credit_mode: Option<CreditMode>,
+    pub credit_allowance: Option<u64>,
+    pub credit_epoch_length: Option<i64>,
+    pub credit_charge_tokens: Option<bool>,
+    pub admission_policy: Option<AdmissionPolicy>,
+    pub admission_root: Option<[u8; 32]>,
+    pub admission_key: Option<Pubkey>,
+    pub lockup_boost_bps: Option<u16>,
+}
+
+// Snapshot of the configurable DAO settings
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct DaoConfig {
+    pub name: String,
+    pub image: String,
+    pub min_yes_votes: u64,
+    pub proposal_creation_fee: u64,
+    pub membership_fee: u64,
+    pub leave_refund_bps: u16,
+    pub execution_delay: i64,
+    pub execution_window: i64,
+    pub quorum_mode: QuorumMode,
+    pub quorum: u64,
+    pub approval_threshold_bps: u16,
+    pub action_threshold_bps: [u16; MAX_PROPOSAL_ACTIONS],
+    pub large_transfer_amount: u64,
+    pub large_transfer_threshold_bps: u16,
+    pub credit_mode: CreditMode,
+    pub credit_allowance: u64,
+    pub credit_epoch_length: i64,
+    pub credit_charge_tokens: bool,
+    pub admission_policy: AdmissionPolicy,
+    pub admission_root: [u8; 32],
+    pub admission_key: Pubkey,
+    pub lockup_boost_bps: u16,
 }
 
 #[account]
@@ -65,8 +204,48 @@ pub struct Membership {
     pub active: bool,
 }
 
+// Settings of a new proposal
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct CreateProposalArgs {
+    pub token_amount: u64,
+    pub end_date: i64,
+    pub title: String,
+    pub description: String,
+    pub action: ProposalAction,
+    pub burn_on_vote: bool,
+    pub snapshot_root: [u8; 32], // all zeros for a proposal voted on by the members
+    pub config: Option<DaoConfigArgs>, // the config change of an update config proposal
+}
+
 #[account]
 pub struct Proposal {
+    pub creator: Pubkey,
+    pub beneficiary: Pubkey,
+    pub dao: Pubkey,
+    pub title: String,
+    pub description: String,
+    pub yes_votes: u64,
+    pub no_votes: u64,
+    pub token_amount: u64,
+    pub status: ProposalStatus,
+    pub action: ProposalAction,
+    pub end_date: i64,
+    pub executed: bool,
+    pub burn_on_vote: bool,
+    pub instruction_count: u8,
+    pub instructions_executed: u8,
+    pub version: u8, // 0 for proposals created before migrate_proposal existed
+    pub executable_at: i64, // set when a passed proposal is queued
+    pub expires_at: i64, // 0 = a queued proposal never expires
+    pub voter_count: u64, // distinct wallets that voted
+    pub abstain_votes: u64,
+    pub snapshot_root: [u8; 32], // merkle root of (wallet, weight) pairs, all zeros = members vote
+}
+
+// Proposal layout written before the status and action enums, the enums serialize to the
+// same single byte so only the values need to be checked when migrating
+#[derive(AnchorDeserialize)]
+pub struct LegacyProposal {
     pub creator: Pubkey,
     pub beneficiary: Pubkey,
     pub dao: Pubkey,
@@ -82,27 +261,166 @@ pub struct Proposal {
     pub burn_on_vote: bool,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProposalStatus {
+    Active,
+    Passed,
+    Failed,
+    Cancelled,
+    Queued,
+    Expired,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ProposalAction {
+    Burn,
+    Transfer,
+    UpdateConfig,
+    ExecuteInstructions,
+    Choice, // multiple choice proposal, the winning option is recorded at finalization
+}
+
+// Participation required, on top of the yes vs no majority, for a proposal to pass
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuorumMode {
+    None,
+    TotalVotes, // yes + no + abstain votes >= quorum
+    DistinctVoters, // voter_count >= quorum
+    ActiveMemberPercent, // voter_count >= quorum basis points of the active members
+}
+
+// Who may join a DAO, on top of paying the membership fee
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AdmissionPolicy {
+    Open,
+    Allowlist, // an admission entry added by an admin
+    MerkleAllowlist, // a proof that the wallet is in admission_root
+    Attestation, // an admission entry added by the admission_key issuer
+    CollectionHolding, // holding an NFT of the verified admission_key collection
+}
+
+// Admission of a wallet to a DAO with the allowlist or attestation policy
+#[account]
+pub struct AdmissionEntry {
+    pub dao: Pubkey,
+    pub user: Pubkey,
+    pub issuer: Pubkey,
+    pub policy: AdmissionPolicy,
+}
+
+// NFT that admitted a wallet to a DAO, each NFT only admits a single wallet
+#[account]
+pub struct AdmissionClaim {
+    pub dao: Pubkey,
+    pub mint: Pubkey,
+    pub user: Pubkey,
+}
+
+// Budget of voice credits every active member gets, a vote costs votes^2 credits
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CreditMode {
+    Disabled,
+    PerProposal,
+    PerEpoch,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VoteSide {
+    No,
+    Yes,
+    Abstain, // counts towards the quorum but not towards the approval
+}
+
+// DAO config change applied when a proposal with the update config action passes
+#[account]
+pub struct ProposalConfigChange {
+    pub proposal: Pubkey,
+    pub config: DaoConfigArgs,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct InstructionAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+// Instruction replayed with the treasury vault as signer when an execute instructions proposal passes
+#[account]
+pub struct ProposalInstruction {
+    pub proposal: Pubkey,
+    pub index: u8,
+    pub program_id: Pubkey,
+    pub accounts: Vec<InstructionAccount>,
+    pub data: Vec<u8>,
+    pub executed: bool,
+}
+
 #[account]
 pub struct UserProposalVotes {
     pub amount: u64,
+    pub tokens_spent: u64, // amount^2 scaled by the mint decimals, 0 when paid with voice credits only
+    pub side: VoteSide, // only meaningful once amount > 0
+    pub payer: Pubkey, // wallet that paid for the votes, the voter or their delegate
 }
 
-impl DAO {
-    // Checks if the provided pubkey is an admin of the DAO
-    // pub fn is_admin(&self, pubkey: &Pubkey) -> Result<()> {
-    //     match self.admins.contains(pubkey) {
-    //         true => Ok(()),
-    //         false => Err(error::ErrorCode::NotAuthorized.into()),
-    //     }
-    // }
-
-    // pub fn is_member(&self, pubkey: &Pubkey) -> Result<()> {
-    //     match self.members.contains(pubkey) {
-    //         true => Ok(()),
-    //         false => Err(error::ErrorCode::NotAuthorized.into()),
-    //     }
-    // }
+// Voting power of a member assigned to another wallet in the DAO
+#[account]
+pub struct Delegation {
+    pub dao: Pubkey,
+    pub delegator: Pubkey,
+    pub delegate: Pubkey,
+}
+
+// Governance tokens a member locked in their stake vault
+#[account]
+pub struct StakeAccount {
+    pub dao: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub lockup_end: i64, // the tokens cannot be unstaked before
+}
+
+// Voice credits spent by a member in the current epoch of the DAO
+#[account]
+pub struct VoiceCredits {
+    pub dao: Pubkey,
+    pub user: Pubkey,
+    pub epoch: u64,
+    pub spent: u64,
+}
 
+// Options of a choice proposal, tallies[i] holds the votes for labels[i]
+#[account]
+pub struct ProposalOptions {
+    pub proposal: Pubkey,
+    pub labels: Vec<String>,
+    pub tallies: Vec<u64>,
+    pub winner: Option<u8>, // set when the proposal passes
+}
+
+// Votes of a user on each option of a choice proposal
+#[account]
+pub struct UserOptionVotes {
+    pub votes: Vec<u64>,
+    pub tokens_spent: u64, // sum of votes^2 scaled by the mint decimals, 0 when paid with voice credits only
+}
+
+// (previous + amount)^2 - previous^2 scaled by the decimal places of the token mint
+pub fn quadratic_cost(previous: u64, amount: u64, decimals: u8) -> Result<u64> {
+    let previous_votes = previous as u128;
+    let total_votes = previous_votes + (amount as u128);
+
+    // new_total^2 - previous_total^2 = amount * (new_total + previous_total)
+    let cost = (amount as u128)
+        .checked_mul(total_votes + previous_votes)
+        .and_then(|cost| cost.checked_mul((10u128).checked_pow(decimals as u32)?))
+        .ok_or(error::ErrorCode::MathOverflow)?;
+
+    u64::try_from(cost).map_err(|_| error::ErrorCode::MathOverflow.into())
+}
+
+impl DAO {
     // check length of name, bio, and avatar
     pub fn check_length(&self, name: &str, image: &str) -> Result<()> {
         if name.chars().count() < MIN_NAME_LENGTH {
@@ -119,6 +437,412 @@ impl DAO {
 
         Ok(())
     }
+
+    // check the fees and refund policy
+    pub fn check_fees(
+        proposal_creation_fee: u64,
+        membership_fee: u64,
+        leave_refund_bps: u16
+    ) -> Result<()> {
+        if membership_fee < 1 {
+            return Err(error::ErrorCode::InvalidFee.into());
+        }
+
+        if proposal_creation_fee < 1 {
+            return Err(error::ErrorCode::InvalidFee.into());
+        }
+
+        if leave_refund_bps > MAX_BASIS_POINTS {
+            return Err(error::ErrorCode::InvalidBasisPoints.into());
+        }
+
+        Ok(())
+    }
+
+    pub fn config(&self) -> DaoConfig {
+        DaoConfig {
+            name: self.name.clone(),
+            image: self.image.clone(),
+            min_yes_votes: self.min_yes_votes,
+            proposal_creation_fee: self.proposal_creation_fee,
+            membership_fee: self.membership_fee,
+            leave_refund_bps: self.leave_refund_bps,
+            execution_delay: self.execution_delay,
+            execution_window: self.execution_window,
+            quorum_mode: self.quorum_mode,
+            quorum: self.quorum,
+            approval_threshold_bps: self.approval_threshold_bps,
+            action_threshold_bps: self.action_threshold_bps,
+            large_transfer_amount: self.large_transfer_amount,
+            large_transfer_threshold_bps: self.large_transfer_threshold_bps,
+            credit_mode: self.credit_mode,
+            credit_allowance: self.credit_allowance,
+            credit_epoch_length: self.credit_epoch_length,
+            credit_charge_tokens: self.credit_charge_tokens,
+            admission_policy: self.admission_policy,
+            admission_root: self.admission_root,
+            admission_key: self.admission_key,
+            lockup_boost_bps: self.lockup_boost_bps,
+        }
+    }
+
+    // validates and applies the provided settings
+    pub fn apply_config(&mut self, args: &DaoConfigArgs) -> Result<()> {
+        let name = args.name.clone().unwrap_or_else(|| self.name.clone());
+        let image = args.image.clone().unwrap_or_else(|| self.image.clone());
+        let proposal_creation_fee = args.proposal_creation_fee.unwrap_or(self.proposal_creation_fee);
+        let membership_fee = args.membership_fee.unwrap_or(self.membership_fee);
+        let leave_refund_bps = args.leave_refund_bps.unwrap_or(self.leave_refund_bps);
+        let execution_delay = args.execution_delay.unwrap_or(self.execution_delay);
+        let execution_window = args.execution_window.unwrap_or(self.execution_window);
+        let quorum_mode = args.quorum_mode.unwrap_or(self.quorum_mode);
+        let quorum = args.quorum.unwrap_or(self.quorum);
+        let approval_threshold_bps = args.approval_threshold_bps.unwrap_or(self.approval_threshold_bps);
+        let action_threshold_bps = args.action_threshold_bps.unwrap_or(self.action_threshold_bps);
+        let large_transfer_threshold_bps = args.large_transfer_threshold_bps.unwrap_or(
+            self.large_transfer_threshold_bps
+        );
+        let credit_mode = args.credit_mode.unwrap_or(self.credit_mode);
+        let credit_allowance = args.credit_allowance.unwrap_or(self.credit_allowance);
+        let credit_epoch_length = args.credit_epoch_length.unwrap_or(self.credit_epoch_length);
+        let admission_policy = args.admission_policy.unwrap_or(self.admission_policy);
+        let admission_root = args.admission_root.unwrap_or(self.admission_root);
+        let admission_key = args.admission_key.unwrap_or(self.admission_key);
+        let lockup_boost_bps = args.lockup_boost_bps.unwrap_or(self.lockup_boost_bps);
+
+        self.check_length(&name, &image)?;
+        DAO::check_fees(proposal_creation_fee, membership_fee, leave_refund_bps)?;
+
+        if execution_delay < 0 || execution_window < 0 {
+            return Err(error::ErrorCode::InvalidExecutionDelay.into());
+        }
+
+        if quorum_mode == QuorumMode::ActiveMemberPercent && quorum > (MAX_BASIS_POINTS as u64) {
+            return Err(error::ErrorCode::InvalidBasisPoints.into());
+        }
+
+        if
+            approval_threshold_bps > MAX_BASIS_POINTS ||
+            large_transfer_threshold_bps > MAX_BASIS_POINTS ||
+            lockup_boost_bps > MAX_BASIS_POINTS ||
+            action_threshold_bps.iter().any(|bps| *bps > MAX_BASIS_POINTS)
+        {
+            return Err(error::ErrorCode::InvalidBasisPoints.into());
+        }
+
+        if
+            (credit_mode != CreditMode::Disabled && credit_allowance == 0) ||
+            (credit_mode == CreditMode::PerEpoch && credit_epoch_length <= 0)
+        {
+            return Err(error::ErrorCode::InvalidCreditConfig.into());
+        }
+
+        if
+            (matches!(admission_policy, AdmissionPolicy::Attestation | AdmissionPolicy::CollectionHolding) &&
+                admission_key == Pubkey::default()) ||
+            (admission_policy == AdmissionPolicy::MerkleAllowlist && admission_root == [0; 32])
+        {
+            return Err(error::ErrorCode::InvalidAdmissionPolicy.into());
+        }
+
+        self.name = name;
+        self.image = image;
+        self.min_yes_votes = args.min_yes_votes.unwrap_or(self.min_yes_votes);
+        self.proposal_creation_fee = proposal_creation_fee;
+        self.membership_fee = membership_fee;
+        self.leave_refund_bps = leave_refund_bps;
+        self.execution_delay = execution_delay;
+        self.execution_window = execution_window;
+        self.quorum_mode = quorum_mode;
+        self.quorum = quorum;
+        self.approval_threshold_bps = approval_threshold_bps;
+        self.action_threshold_bps = action_threshold_bps;
+        self.large_transfer_amount = args.large_transfer_amount.unwrap_or(self.large_transfer_amount);
+        self.large_transfer_threshold_bps = large_transfer_threshold_bps;
+        self.credit_mode = credit_mode;
+        self.credit_allowance = credit_allowance;
+        self.credit_epoch_length = credit_epoch_length;
+        self.credit_charge_tokens = args.credit_charge_tokens.unwrap_or(self.credit_charge_tokens);
+        self.admission_policy = admission_policy;
+        self.admission_root = admission_root;
+        self.admission_key = admission_key;
+        self.lockup_boost_bps = lockup_boost_bps;
+
+        Ok(())
+    }
+
+    // checks that the proof leads from the wallet to the admission root
+    pub fn verify_admission_proof(&self, user: &Pubkey, proof: &[[u8; 32]]) -> bool {
+        verify_merkle_proof(self.admission_root, keccak::hashv(&[user.as_ref()]).0, proof)
+    }
+
+    // votes cost tokens unless the DAO uses voice credits only
+    pub fn charges_tokens(&self) -> bool {
+        self.credit_mode == CreditMode::Disabled || self.credit_charge_tokens
+    }
+
+    // checks that `credits` more voice credits fit in the budget of the member. `proposal_credits`
+    // are the credits the member already spent on the proposal, epoch budgets are tracked by
+    // `voice_credits` instead.
+    pub fn spend_credits(
+        &self,
+        voice_credits: Option<&mut VoiceCredits>,
+        proposal_credits: u64,
+        credits: u64,
+        now: i64
+    ) -> Result<()> {
+        let (spent, credits_account) = match self.credit_mode {
+            CreditMode::Disabled => {
+                return Ok(());
+            }
+            CreditMode::PerProposal => (proposal_credits, None),
+            CreditMode::PerEpoch => {
+                let voice_credits = voice_credits.ok_or(error::ErrorCode::VoiceCreditsRequired)?;
+                let epoch = (now / self.credit_epoch_length) as u64;
+
+                // the budget is refilled at the start of every epoch
+                if voice_credits.epoch != epoch {
+                    voice_credits.epoch = epoch;
+                    voice_credits.spent = 0;
+                }
+
+                (voice_credits.spent, Some(voice_credits))
+            }
+        };
+
+        let total = spent.checked_add(credits).ok_or(error::ErrorCode::MathOverflow)?;
+
+        if total > self.credit_allowance {
+            return Err(error::ErrorCode::InsufficientVoiceCredits.into());
+        }
+
+        if let Some(voice_credits) = credits_account {
+            voice_credits.spent = total;
+        }
+
+        Ok(())
+    }
+
+    // highest of the DAO, action and large transfer thresholds that applies to the proposal
+    pub fn approval_threshold(&self, proposal: &Proposal) -> u16 {
+        let mut threshold = self.approval_threshold_bps.max(
+            self.action_threshold_bps[proposal.action as usize]
+        );
+
+        if
+            proposal.action == ProposalAction::Transfer &&
+            self.large_transfer_amount > 0 &&
+            proposal.token_amount > self.large_transfer_amount
+        {
+            threshold = threshold.max(self.large_transfer_threshold_bps);
+        }
+
+        threshold
+    }
+
+    // yes votes need a majority and at least the approval threshold share of yes + no votes
+    pub fn approved(&self, proposal: &Proposal) -> bool {
+        let yes_votes = proposal.yes_votes as u128;
+        let total_votes = yes_votes + (proposal.no_votes as u128);
+
+        proposal.yes_votes > proposal.no_votes &&
+            yes_votes * (MAX_BASIS_POINTS as u128) >=
+                (self.approval_threshold(proposal) as u128) * total_votes
+    }
+
+    // checks the participation of a proposal against the quorum of the DAO
+    pub fn quorum_reached(&self, total_votes: u64, voter_count: u64) -> bool {
+        match self.quorum_mode {
+            QuorumMode::None => true,
+            QuorumMode::TotalVotes => total_votes >= self.quorum,
+            QuorumMode::DistinctVoters => voter_count >= self.quorum,
+            QuorumMode::ActiveMemberPercent => {
+                (voter_count as u128) * (MAX_BASIS_POINTS as u128) >=
+                    (self.quorum as u128) * (self.member_count as u128)
+            }
+        }
+    }
+
+    pub fn add_member(&mut self) -> Result<()> {
+        self.member_count = self.member_count.checked_add(1).ok_or(error::ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    // members that joined before the counter was backfilled may not be counted, so they can
+    // always leave without underflowing it
+    pub fn remove_member(&mut self) {
+        self.member_count = self.member_count.saturating_sub(1);
+    }
+
+    // membership fee refunded to a member leaving the DAO
+    pub fn leave_refund(&self) -> Result<u64> {
+        let refund =
+            ((self.membership_fee as u128) * (self.leave_refund_bps as u128)) /
+            (MAX_BASIS_POINTS as u128);
+
+        u64::try_from(refund).map_err(|_| error::ErrorCode::MathOverflow.into())
+    }
+}
+
+impl CreatorRegistry {
+    // index of the next DAO of the creator, used in its address
+    pub fn next_dao_index(&self) -> u64 {
+        self.daos.len() as u64
+    }
+}
+
+impl Admin {
+    // Checks if the provided pubkey is an admin of the given DAO
+    pub fn is_admin(&self, dao: &Pubkey, user: &Pubkey) -> Result<()> {
+        if self.dao != *dao || self.admin != *user {
+            return Err(error::ErrorCode::NotAuthorized.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl AdmissionEntry {
+    // Checks that the entry admits the user under the current policy of the DAO
+    pub fn admits(&self, dao: &DAO, dao_key: &Pubkey, user: &Pubkey) -> Result<()> {
+        let issued_by_policy = match dao.admission_policy {
+            AdmissionPolicy::Allowlist => true,
+            AdmissionPolicy::Attestation => self.issuer == dao.admission_key,
+            _ => false,
+        };
+
+        if
+            self.dao != *dao_key ||
+            self.user != *user ||
+            self.policy != dao.admission_policy ||
+            !issued_by_policy
+        {
+            return Err(error::ErrorCode::NotAdmitted.into());
+        }
+
+        Ok(())
+    }
+}
+
+impl AdmissionClaim {
+    // Claims the NFT for the user, a wallet that already claimed it can rejoin with it
+    pub fn claim(&mut self, dao: &Pubkey, mint: &Pubkey, user: &Pubkey) -> Result<()> {
+        if self.user != Pubkey::default() && self.user != *user {
+            return Err(error::ErrorCode::AdmissionAlreadyClaimed.into());
+        }
+
+        self.dao = *dao;
+        self.mint = *mint;
+        self.user = *user;
+
+        Ok(())
+    }
+}
+
+// Reads the mint and the verified collection of a Metaplex metadata account,
+// the collection is None when the NFT has no collection or it is not verified
+pub fn read_metadata_collection(data: &[u8]) -> Option<(Pubkey, Option<Pubkey>)> {
+    let mut reader = MetadataReader { data, offset: 0 };
+
+    // key and update authority
+    reader.skip(1 + 32)?;
+    let mint = Pubkey::new_from_array(reader.take(32)?.try_into().ok()?);
+
+    // name, symbol and uri
+    for _ in 0..3 {
+        let length = u32::from_le_bytes(reader.take(4)?.try_into().ok()?);
+        reader.skip(length as usize)?;
+    }
+
+    // seller fee basis points
+    reader.skip(2)?;
+
+    // creators, each one is an address, a verified flag and a share
+    if reader.option()? {
+        let count = u32::from_le_bytes(reader.take(4)?.try_into().ok()?);
+        reader.skip((count as usize).checked_mul(32 + 1 + 1)?)?;
+    }
+
+    // primary sale happened and is mutable
+    reader.skip(2)?;
+
+    // edition nonce and token standard
+    for _ in 0..2 {
+        if reader.option()? {
+            reader.skip(1)?;
+        }
+    }
+
+    if !reader.option()? {
+        return Some((mint, None));
+    }
+
+    let verified = reader.take(1)?[0] == 1;
+    let collection = Pubkey::new_from_array(reader.take(32)?.try_into().ok()?);
+
+    Some((mint, verified.then_some(collection)))
+}
+
+struct MetadataReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> MetadataReader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.offset..self.offset.checked_add(length)?)?;
+        self.offset += length;
+
+        Some(bytes)
+    }
+
+    fn skip(&mut self, length: usize) -> Option<()> {
+        self.take(length).map(|_| ())
+    }
+
+    // borsh options are prefixed with 0 for None and 1 for Some
+    fn option(&mut self) -> Option<bool> {
+        Some(self.take(1)?[0] == 1)
+    }
+}
+
+// Verifies a merkle proof where each pair of nodes is hashed in sorted order
+pub fn verify_merkle_proof(root: [u8; 32], leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+    let computed = proof.iter().fold(leaf, |node, sibling| {
+        match node <= *sibling {
+            true => keccak::hashv(&[&node, sibling]).0,
+            false => keccak::hashv(&[sibling, &node]).0,
+        }
+    });
+
+    computed == root
+}
+
+impl StakeAccount {
+    // staked amount boosted by up to `lockup_boost_bps` for the lockup left at `now`
+    pub fn voting_weight(&self, lockup_boost_bps: u16, now: i64) -> Result<u64> {
+        let remaining_lockup = self.lockup_end.saturating_sub(now).clamp(0, MAX_LOCKUP_PERIOD);
+        let boost_bps =
+            ((lockup_boost_bps as u128) * (remaining_lockup as u128)) / (MAX_LOCKUP_PERIOD as u128);
+
+        let weight =
+            ((self.amount as u128) * ((MAX_BASIS_POINTS as u128) + boost_bps)) /
+            (MAX_BASIS_POINTS as u128);
+
+        u64::try_from(weight).map_err(|_| error::ErrorCode::MathOverflow.into())
+    }
+}
+
+impl Membership {
+    // Checks that the membership belongs to the user in the given DAO and has not been deactivated
+    pub fn is_active_member(&self, dao: &Pubkey, user: &Pubkey) -> Result<()> {
+        if self.dao != *dao || self.user != *user || !self.active {
+            return Err(error::ErrorCode::NotActiveMember.into());
+        }
+
+        Ok(())
+    }
 }
 
 impl Proposal {
@@ -138,4 +862,1180 @@ impl Proposal {
 
         Ok(())
     }
+
+    // adds votes to the tally of the given side
+    pub fn add_votes(&mut self, side: VoteSide, amount: u64) -> Result<()> {
+        let tally = match side {
+            VoteSide::No => &mut self.no_votes,
+            VoteSide::Yes => &mut self.yes_votes,
+            VoteSide::Abstain => &mut self.abstain_votes,
+        };
+
+        *tally = tally.checked_add(amount).ok_or(error::ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn has_snapshot(&self) -> bool {
+        self.snapshot_root != [0; 32]
+    }
+
+    // checks that the proof leads from the (wallet, weight) leaf to the snapshot root
+    pub fn verify_snapshot_proof(&self, user: &Pubkey, weight: u64, proof: &[[u8; 32]]) -> bool {
+        let leaf = keccak::hashv(&[user.as_ref(), &weight.to_le_bytes()]).0;
+
+        verify_merkle_proof(self.snapshot_root, leaf, proof)
+    }
+
+    pub fn has_votes(&self) -> bool {
+        self.yes_votes > 0 || self.no_votes > 0 || self.abstain_votes > 0 || self.voter_count > 0
+    }
+
+    // checks that the proposal can be executed at the given time,
+    // returns false once the execution window of a queued proposal has expired
+    pub fn check_executable(&self, now: i64) -> Result<bool> {
+        if self.executed {
+            return Err(error::ErrorCode::ProposalAlreadyExecuted.into());
+        }
+
+        match self.status {
+            ProposalStatus::Passed | ProposalStatus::Queued => {}
+            ProposalStatus::Active => {
+                return Err(error::ErrorCode::ProposalNotFinalized.into());
+            }
+            ProposalStatus::Cancelled => {
+                return Err(error::ErrorCode::ProposalCancelled.into());
+            }
+            _ => {
+                return Err(error::ErrorCode::ProposalNotPassed.into());
+            }
+        }
+
+        if now < self.executable_at {
+            return Err(error::ErrorCode::ProposalTimelocked.into());
+        }
+
+        Ok(self.expires_at == 0 || now <= self.expires_at)
+    }
+
+    // yes + no + abstain votes
+    pub fn total_votes(&self) -> Result<u64> {
+        self.yes_votes
+            .checked_add(self.no_votes)
+            .and_then(|votes| votes.checked_add(self.abstain_votes))
+            .ok_or(error::ErrorCode::MathOverflow.into())
+    }
+
+    // removes votes from the tally of the given side
+    pub fn remove_votes(&mut self, side: VoteSide, amount: u64) -> Result<()> {
+        let tally = match side {
+            VoteSide::No => &mut self.no_votes,
+            VoteSide::Yes => &mut self.yes_votes,
+            VoteSide::Abstain => &mut self.abstain_votes,
+        };
+
+        *tally = tally.checked_sub(amount).ok_or(error::ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+}
+
+impl ProposalOptions {
+    pub fn check_labels(labels: &[String]) -> Result<()> {
+        if labels.len() < MIN_PROPOSAL_OPTIONS || labels.len() > MAX_PROPOSAL_OPTIONS {
+            return Err(error::ErrorCode::InvalidOptionCount.into());
+        }
+
+        for label in labels {
+            if label.is_empty() || label.chars().count() > MAX_OPTION_LABEL_LENGTH {
+                return Err(error::ErrorCode::InvalidOptionLabel.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_votes(&mut self, option: u8, amount: u64) -> Result<()> {
+        let tally = self.tallies
+            .get_mut(option as usize)
+            .ok_or(error::ErrorCode::InvalidOption)?;
+
+        *tally = tally.checked_add(amount).ok_or(error::ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    pub fn total_votes(&self) -> Result<u64> {
+        self.tallies
+            .iter()
+            .try_fold(0u64, |total, tally| total.checked_add(*tally))
+            .ok_or(error::ErrorCode::MathOverflow.into())
+    }
+
+    // option with strictly the most votes, a tie or no votes at all has no winner
+    pub fn plurality_winner(&self) -> Option<u8> {
+        let most_votes = *self.tallies.iter().max()?;
+
+        if most_votes == 0 || self.tallies.iter().filter(|tally| **tally == most_votes).count() > 1 {
+            return None;
+        }
+
+        self.tallies
+            .iter()
+            .position(|tally| *tally == most_votes)
+            .map(|option| option as u8)
+    }
+}
+
+impl UserOptionVotes {
+    // marginal cost of adding `amount` votes to `option`, the other options are unaffected
+    // so the total paid stays the sum of the squared votes per option
+    pub fn vote_cost(&self, option: u8, amount: u64, decimals: u8) -> Result<u64> {
+        let previous = *self.votes.get(option as usize).ok_or(error::ErrorCode::InvalidOption)?;
+
+        quadratic_cost(previous, amount, decimals)
+    }
+
+    pub fn add_votes(&mut self, option: u8, amount: u64, cost: u64) -> Result<()> {
+        let votes = self.votes
+            .get_mut(option as usize)
+            .ok_or(error::ErrorCode::InvalidOption)?;
+
+        *votes = votes.checked_add(amount).ok_or(error::ErrorCode::MathOverflow)?;
+        self.tokens_spent = self.tokens_spent
+            .checked_add(cost)
+            .ok_or(error::ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    // voice credits spent on the proposal, the sum of votes^2
+    pub fn credits_spent(&self) -> Result<u64> {
+        self.votes
+            .iter()
+            .try_fold(0u64, |total, votes| total.checked_add(votes.checked_mul(*votes)?))
+            .ok_or(error::ErrorCode::MathOverflow.into())
+    }
+
+    pub fn has_voted(&self) -> bool {
+        self.votes.iter().any(|votes| *votes > 0)
+    }
+}
+
+impl LegacyProposal {
+    // Converts the legacy proposal into the current layout. Legacy proposals with an action other
+    // than burn or transfer could never be executed, active ones are cancelled so voters are not
+    // locked into a proposal that cannot pass.
+    pub fn migrate(self) -> Result<Proposal> {
+        let status = match self.status {
+            0 => ProposalStatus::Active,
+            1 => ProposalStatus::Passed,
+            2 => ProposalStatus::Failed,
+            _ => {
+                return Err(error::ErrorCode::InvalidProposal.into());
+            }
+        };
+
+        let (status, action) = match self.action {
+            0 => (status, ProposalAction::Burn),
+            1 => (status, ProposalAction::Transfer),
+            _ if status == ProposalStatus::Active => (ProposalStatus::Cancelled, ProposalAction::Transfer),
+            _ => (status, ProposalAction::Transfer),
+        };
+
+        Ok(Proposal {
+            creator: self.creator,
+            beneficiary: self.beneficiary,
+            dao: self.dao,
+            title: self.title,
+            description: self.description,
+            yes_votes: self.yes_votes,
+            no_votes: self.no_votes,
+            token_amount: self.token_amount,
+            status,
+            action,
+            end_date: self.end_date,
+            executed: self.executed,
+            burn_on_vote: self.burn_on_vote,
+            instruction_count: 0,
+            instructions_executed: 0,
+            version: PROPOSAL_VERSION,
+            executable_at: 0,
+            expires_at: 0,
+            voter_count: 0,
+            abstain_votes: 0,
+            snapshot_root: [0; 32],
+        })
+    }
+}
+
+impl LegacyDao {
+    // Converts the legacy DAO into the current layout with the settings create_dao starts with.
+    // Legacy DAOs had no admins, the creator becomes the only admin.
+    pub fn migrate(self, governance_mint: Pubkey, member_count: u64) -> DAO {
+        DAO {
+            creator: self.creator,
+            name: self.name,
+            image: self.image,
+            treasury_vault: self.treasury_vault,
+            burn_vault: self.burn_vault,
+            total_proposals: self.total_proposals,
+            min_yes_votes: self.min_yes_votes,
+            proposal_creation_fee: self.proposal_creation_fee,
+            membership_fee: self.membership_fee,
+            leave_refund_bps: 0,
+            admin_count: 1,
+            execution_delay: 0,
+            execution_window: 0,
+            quorum_mode: QuorumMode::None,
+            quorum: 0,
+            member_count,
+            approval_threshold_bps: 0,
+            action_threshold_bps: [0; MAX_PROPOSAL_ACTIONS],
+            large_transfer_amount: 0,
+            large_transfer_threshold_bps: 0,
+            credit_mode: CreditMode::Disabled,
+            credit_allowance: 0,
+            credit_epoch_length: 0,
+            credit_charge_tokens: false,
+            admission_policy: AdmissionPolicy::Open,
+            admission_root: [0; 32],
+            admission_key: Pubkey::default(),
+            lockup_boost_bps: 0,
+            governance_mint,
+            version: DAO_VERSION,
+        }
+    }
+}
+
+impl LegacyUserProposalVotes {
+    // reads the record data following the discriminator
+    pub fn read(data: &[u8]) -> Result<Self> {
+        let mut data = data;
+
+        match data.len() {
+            8 => Ok(LegacyUserProposalVotes::AmountOnly { amount: u64::deserialize(&mut data)? }),
+            24 =>
+                Ok(LegacyUserProposalVotes::Sided {
+                    amount: u64::deserialize(&mut data)?,
+                    tokens_spent: u64::deserialize(&mut data)?,
+                    side: VoteSide::deserialize(&mut data)?,
+                }),
+            _ => Err(error::ErrorCode::InvalidVoteRecord.into()),
+        }
+    }
+
+    // Converts the record of `voter` on `proposal` into the current layout, the votes were paid by
+    // the voter. The side of the first layout is only known when every vote went to one side.
+    pub fn migrate(self, voter: Pubkey, proposal: &Proposal) -> Result<UserProposalVotes> {
+        let (amount, tokens_spent, side) = match self {
+            LegacyUserProposalVotes::AmountOnly { amount } => {
+                let side = match (proposal.yes_votes, proposal.no_votes) {
+                    _ if amount == 0 => VoteSide::No,
+                    (yes_votes, 0) if yes_votes >= amount => VoteSide::Yes,
+                    (0, no_votes) if no_votes >= amount => VoteSide::No,
+                    _ => {
+                        return Err(error::ErrorCode::UnknownVoteSide.into());
+                    }
+                };
+
+                // nothing was deposited into the escrow, so nothing can be withdrawn
+                (amount, 0, side)
+            }
+            LegacyUserProposalVotes::Sided { amount, tokens_spent, side } => (amount, tokens_spent, side),
+        };
+
+        let payer = match amount {
+            0 => Pubkey::default(),
+            _ => voter,
+        };
+
+        Ok(UserProposalVotes { amount, tokens_spent, side, payer })
+    }
+}
+
+impl UserProposalVotes {
+    // marginal cost of adding `amount` votes on top of the votes already cast,
+    // i.e. (new_total^2 - previous_total^2) scaled by the decimal places of the token mint
+    pub fn vote_cost(&self, amount: u64, decimals: u8) -> Result<u64> {
+        quadratic_cost(self.amount, amount, decimals)
+    }
+
+    // the votes of a record are paid by a single wallet so the deposit can be returned to it
+    pub fn set_payer(&mut self, payer: &Pubkey) -> Result<()> {
+        if self.amount > 0 && self.payer != *payer {
+            return Err(error::ErrorCode::VotePayerMismatch.into());
+        }
+
+        self.payer = *payer;
+
+        Ok(())
+    }
+
+    // wallet the deposit and the rent are returned to, records without votes belong to the voter
+    pub fn refund_recipient(&self, voter: &Pubkey) -> Pubkey {
+        match self.amount > 0 && self.payer != Pubkey::default() {
+            true => self.payer,
+            false => *voter,
+        }
+    }
+
+    // voice credits spent on the proposal, amount^2
+    pub fn credits_spent(&self) -> Result<u64> {
+        self.amount.checked_mul(self.amount).ok_or(error::ErrorCode::MathOverflow.into())
+    }
+
+    // records `amount` more votes on `side` that were paid with `cost`
+    pub fn add_votes(&mut self, side: VoteSide, amount: u64, cost: u64) -> Result<()> {
+        self.amount = self.amount.checked_add(amount).ok_or(error::ErrorCode::MathOverflow)?;
+        self.tokens_spent = self.tokens_spent
+            .checked_add(cost)
+            .ok_or(error::ErrorCode::MathOverflow)?;
+        self.side = side;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn votes(amount: u64) -> UserProposalVotes {
+        UserProposalVotes { amount, tokens_spent: 0, side: VoteSide::Yes, payer: Pubkey::default() }
+    }
+
+    fn proposal(yes_votes: u64, no_votes: u64) -> Proposal {
+        Proposal {
+            creator: Pubkey::default(),
+            beneficiary: Pubkey::default(),
+            dao: Pubkey::default(),
+            title: String::new(),
+            description: String::new(),
+            yes_votes,
+            no_votes,
+            token_amount: 0,
+            status: ProposalStatus::Active,
+            action: ProposalAction::Burn,
+            end_date: 0,
+            executed: false,
+            burn_on_vote: false,
+            instruction_count: 0,
+            instructions_executed: 0,
+            version: PROPOSAL_VERSION,
+            executable_at: 0,
+            expires_at: 0,
+            voter_count: 0,
+            abstain_votes: 0,
+            snapshot_root: [0; 32],
+        }
+    }
+
+    fn membership(dao: Pubkey, user: Pubkey, active: bool) -> Membership {
+        Membership { dao, user, joined_date: 0, active }
+    }
+
+    fn stake(amount: u64, lockup_end: i64) -> StakeAccount {
+        StakeAccount { dao: Pubkey::default(), user: Pubkey::default(), amount, lockup_end }
+    }
+
+    #[test]
+    fn lockup_boost_decays_with_the_remaining_lockup() {
+        assert_eq!(stake(1_000, MAX_LOCKUP_PERIOD).voting_weight(10_000, 0).unwrap(), 2_000);
+        assert_eq!(stake(1_000, MAX_LOCKUP_PERIOD).voting_weight(0, 0).unwrap(), 1_000);
+        assert_eq!(stake(1_000, MAX_LOCKUP_PERIOD / 2).voting_weight(10_000, 0).unwrap(), 1_500);
+        assert_eq!(stake(1_000, 100).voting_weight(10_000, 200).unwrap(), 1_000);
+        assert_eq!(stake(1_000, 2 * MAX_LOCKUP_PERIOD).voting_weight(5_000, 0).unwrap(), 1_500);
+    }
+
+    #[test]
+    fn votes_keep_their_first_payer() {
+        let (voter, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut user_votes = votes(0);
+
+        user_votes.set_payer(&delegate).unwrap();
+        user_votes.set_payer(&voter).unwrap();
+        assert_eq!(user_votes.payer, voter);
+
+        user_votes.amount = 3;
+        assert_eq!(user_votes.set_payer(&delegate), Err(error::ErrorCode::VotePayerMismatch.into()));
+        assert!(user_votes.set_payer(&voter).is_ok());
+    }
+
+    #[test]
+    fn empty_vote_records_are_refunded_to_the_voter() {
+        let (voter, delegate) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut user_votes = votes(0);
+
+        assert_eq!(user_votes.refund_recipient(&voter), voter);
+
+        user_votes.set_payer(&delegate).unwrap();
+        assert_eq!(user_votes.refund_recipient(&voter), voter);
+
+        user_votes.amount = 2;
+        assert_eq!(user_votes.refund_recipient(&voter), delegate);
+    }
+
+    #[test]
+    fn active_member_passes_membership_check() {
+        let (dao, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert!(membership(dao, user, true).is_active_member(&dao, &user).is_ok());
+    }
+
+    #[test]
+    fn inactive_or_foreign_member_fails_membership_check() {
+        let (dao, user, other) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert_eq!(
+            membership(dao, user, false).is_active_member(&dao, &user),
+            Err(error::ErrorCode::NotActiveMember.into())
+        );
+        assert_eq!(
+            membership(other, user, true).is_active_member(&dao, &user),
+            Err(error::ErrorCode::NotActiveMember.into())
+        );
+        assert_eq!(
+            membership(dao, other, true).is_active_member(&dao, &user),
+            Err(error::ErrorCode::NotActiveMember.into())
+        );
+    }
+
+    fn dao(membership_fee: u64, leave_refund_bps: u16) -> DAO {
+        DAO {
+            creator: Pubkey::default(),
+            name: String::from("Quadratus DAO"),
+            image: String::new(),
+            treasury_vault: Pubkey::default(),
+            burn_vault: Pubkey::default(),
+            total_proposals: 0,
+            min_yes_votes: 0,
+            proposal_creation_fee: 1,
+            membership_fee,
+            leave_refund_bps,
+            admin_count: 1,
+            execution_delay: 0,
+            execution_window: 0,
+            quorum_mode: QuorumMode::None,
+            quorum: 0,
+            member_count: 0,
+            approval_threshold_bps: 0,
+            action_threshold_bps: [0; MAX_PROPOSAL_ACTIONS],
+            large_transfer_amount: 0,
+            large_transfer_threshold_bps: 0,
+            credit_mode: CreditMode::Disabled,
+            credit_allowance: 0,
+            credit_epoch_length: 0,
+            credit_charge_tokens: false,
+            admission_policy: AdmissionPolicy::Open,
+            admission_root: [0; 32],
+            admission_key: Pubkey::default(),
+            lockup_boost_bps: 0,
+            governance_mint: Pubkey::default(),
+            version: DAO_VERSION,
+        }
+    }
+
+    #[test]
+    fn apply_config_only_updates_provided_settings() {
+        let mut config_dao = dao(100, 0);
+
+        config_dao
+            .apply_config(&DaoConfigArgs {
+                min_yes_votes: Some(10),
+                leave_refund_bps: Some(2500),
+                ..DaoConfigArgs::default()
+            })
+            .unwrap();
+
+        assert_eq!(config_dao.name, "Quadratus DAO");
+        assert_eq!(config_dao.membership_fee, 100);
+        assert_eq!(config_dao.min_yes_votes, 10);
+        assert_eq!(config_dao.leave_refund_bps, 2500);
+    }
+
+    #[test]
+    fn quorum_modes_measure_participation() {
+        let mut quorum_dao = dao(100, 0);
+        let mut voted = proposal(30, 10);
+        voted.voter_count = 3;
+        quorum_dao.member_count = 10;
+
+        let cases = [
+            (QuorumMode::None, 0, true),
+            (QuorumMode::TotalVotes, 40, true),
+            (QuorumMode::TotalVotes, 41, false),
+            (QuorumMode::DistinctVoters, 3, true),
+            (QuorumMode::DistinctVoters, 4, false),
+            (QuorumMode::ActiveMemberPercent, 3000, true),
+            (QuorumMode::ActiveMemberPercent, 3001, false),
+        ];
+
+        for (quorum_mode, quorum, reached) in cases {
+            quorum_dao.quorum_mode = quorum_mode;
+            quorum_dao.quorum = quorum;
+
+            assert_eq!(quorum_dao.quorum_reached(voted.total_votes().unwrap(), voted.voter_count), reached);
+        }
+    }
+
+    #[test]
+    fn abstain_votes_count_towards_quorum_only() {
+        let mut quorum_dao = dao(100, 0);
+        quorum_dao.quorum_mode = QuorumMode::TotalVotes;
+        quorum_dao.quorum = 10;
+        quorum_dao.approval_threshold_bps = 6000;
+
+        let mut voted = proposal(3, 2);
+        assert!(!quorum_dao.quorum_reached(voted.total_votes().unwrap(), voted.voter_count));
+
+        voted.add_votes(VoteSide::Abstain, 5).unwrap();
+        assert!(quorum_dao.quorum_reached(voted.total_votes().unwrap(), voted.voter_count));
+        assert!(quorum_dao.approved(&voted));
+    }
+
+    #[test]
+    fn abstain_votes_count_as_votes() {
+        let mut voted = proposal(0, 0);
+        assert!(!voted.has_votes());
+
+        voted.add_votes(VoteSide::Abstain, 1).unwrap();
+        assert!(voted.has_votes());
+    }
+
+    #[test]
+    fn queued_proposals_execute_inside_their_window() {
+        let mut queued = proposal(1, 0);
+        queued.status = ProposalStatus::Queued;
+        queued.executable_at = 100;
+        queued.expires_at = 200;
+
+        assert_eq!(
+            queued.check_executable(99),
+            Err(error::ErrorCode::ProposalTimelocked.into())
+        );
+        assert!(queued.check_executable(100).unwrap());
+        assert!(queued.check_executable(200).unwrap());
+        assert!(!queued.check_executable(201).unwrap());
+
+        queued.expires_at = 0;
+        assert!(queued.check_executable(i64::MAX).unwrap());
+
+        queued.executed = true;
+        assert_eq!(
+            queued.check_executable(150),
+            Err(error::ErrorCode::ProposalAlreadyExecuted.into())
+        );
+    }
+
+    #[test]
+    fn only_passed_or_queued_proposals_execute() {
+        let mut passed = proposal(1, 0);
+        passed.status = ProposalStatus::Passed;
+        assert!(passed.check_executable(0).unwrap());
+
+        for (status, code) in [
+            (ProposalStatus::Active, error::ErrorCode::ProposalNotFinalized),
+            (ProposalStatus::Cancelled, error::ErrorCode::ProposalCancelled),
+            (ProposalStatus::Failed, error::ErrorCode::ProposalNotPassed),
+            (ProposalStatus::Expired, error::ErrorCode::ProposalNotPassed),
+        ] {
+            passed.status = status;
+            assert_eq!(passed.check_executable(0), Err(code.into()));
+        }
+    }
+
+    fn options(tallies: Vec<u64>) -> ProposalOptions {
+        ProposalOptions {
+            proposal: Pubkey::default(),
+            labels: tallies.iter().map(|tally| tally.to_string()).collect(),
+            tallies,
+            winner: None,
+        }
+    }
+
+    #[test]
+    fn plurality_winner_needs_a_strict_plurality() {
+        assert_eq!(options(vec![3, 7, 5]).plurality_winner(), Some(1));
+        assert_eq!(options(vec![7, 7, 5]).plurality_winner(), None);
+        assert_eq!(options(vec![0, 0]).plurality_winner(), None);
+    }
+
+    #[test]
+    fn option_labels_are_validated() {
+        let labels = |count: usize, label: &str| vec![String::from(label); count];
+
+        assert!(ProposalOptions::check_labels(&labels(2, "Grants")).is_ok());
+        assert_eq!(
+            ProposalOptions::check_labels(&labels(1, "Grants")),
+            Err(error::ErrorCode::InvalidOptionCount.into())
+        );
+        assert_eq!(
+            ProposalOptions::check_labels(&labels(MAX_PROPOSAL_OPTIONS + 1, "Grants")),
+            Err(error::ErrorCode::InvalidOptionCount.into())
+        );
+        assert_eq!(
+            ProposalOptions::check_labels(&labels(2, "")),
+            Err(error::ErrorCode::InvalidOptionLabel.into())
+        );
+    }
+
+    #[test]
+    fn large_transfers_need_a_supermajority() {
+        let mut threshold_dao = dao(100, 0);
+        threshold_dao.large_transfer_amount = 1_000;
+        threshold_dao.large_transfer_threshold_bps = 6600;
+
+        let mut transfer = proposal(60, 40);
+        transfer.action = ProposalAction::Transfer;
+        transfer.token_amount = 1_000;
+        assert!(threshold_dao.approved(&transfer));
+
+        transfer.token_amount = 1_001;
+        assert!(!threshold_dao.approved(&transfer));
+
+        transfer.yes_votes = 66;
+        transfer.no_votes = 34;
+        assert!(threshold_dao.approved(&transfer));
+
+        threshold_dao.action_threshold_bps[ProposalAction::Transfer as usize] = 7500;
+        assert!(!threshold_dao.approved(&transfer));
+    }
+
+    #[test]
+    fn per_proposal_credits_cap_the_votes_on_a_proposal() {
+        let mut credit_dao = dao(100, 0);
+        credit_dao.credit_mode = CreditMode::PerProposal;
+        credit_dao.credit_allowance = 100;
+
+        assert!(credit_dao.spend_credits(None, 64, 36, 0).is_ok());
+        assert_eq!(
+            credit_dao.spend_credits(None, 64, 37, 0),
+            Err(error::ErrorCode::InsufficientVoiceCredits.into())
+        );
+    }
+
+    #[test]
+    fn epoch_credits_refill_every_epoch() {
+        let mut credit_dao = dao(100, 0);
+        credit_dao.credit_mode = CreditMode::PerEpoch;
+        credit_dao.credit_allowance = 100;
+        credit_dao.credit_epoch_length = 1_000;
+
+        let mut credits = VoiceCredits {
+            dao: Pubkey::default(),
+            user: Pubkey::default(),
+            epoch: 0,
+            spent: 0,
+        };
+
+        credit_dao.spend_credits(Some(&mut credits), 0, 81, 5_500).unwrap();
+        assert_eq!(
+            credit_dao.spend_credits(Some(&mut credits), 0, 25, 5_999),
+            Err(error::ErrorCode::InsufficientVoiceCredits.into())
+        );
+
+        credit_dao.spend_credits(Some(&mut credits), 0, 25, 6_000).unwrap();
+        assert_eq!((credits.epoch, credits.spent), (6, 25));
+
+        assert_eq!(
+            credit_dao.spend_credits(None, 0, 1, 6_000),
+            Err(error::ErrorCode::VoiceCreditsRequired.into())
+        );
+    }
+
+    #[test]
+    fn admission_proof_verifies_allowed_wallets() {
+        let wallets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<[u8; 32]> = wallets
+            .iter()
+            .map(|wallet| keccak::hashv(&[wallet.as_ref()]).0)
+            .collect();
+        let pair = |a: [u8; 32], b: [u8; 32]| match a <= b {
+            true => keccak::hashv(&[&a, &b]).0,
+            false => keccak::hashv(&[&b, &a]).0,
+        };
+
+        let mut admission_dao = dao(100, 0);
+        admission_dao.admission_root = pair(pair(leaves[0], leaves[1]), leaves[2]);
+
+        assert!(admission_dao.verify_admission_proof(&wallets[0], &[leaves[1], leaves[2]]));
+        assert!(admission_dao.verify_admission_proof(&wallets[2], &[pair(leaves[0], leaves[1])]));
+        assert!(!admission_dao.verify_admission_proof(&wallets[1], &[leaves[1], leaves[2]]));
+        assert!(!admission_dao.verify_admission_proof(&Pubkey::new_unique(), &[leaves[1], leaves[2]]));
+    }
+
+    #[test]
+    fn snapshot_proof_binds_the_weight() {
+        let (user, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let leaf = |wallet: &Pubkey, weight: u64| keccak::hashv(&[wallet.as_ref(), &weight.to_le_bytes()]).0;
+        let (a, b) = (leaf(&user, 500), leaf(&other, 900));
+
+        let mut snapshot = proposal(0, 0);
+        snapshot.snapshot_root = match a <= b {
+            true => keccak::hashv(&[&a, &b]).0,
+            false => keccak::hashv(&[&b, &a]).0,
+        };
+
+        assert!(snapshot.has_snapshot());
+        assert!(snapshot.verify_snapshot_proof(&user, 500, &[b]));
+        assert!(!snapshot.verify_snapshot_proof(&user, 900, &[b]));
+        assert!(!snapshot.verify_snapshot_proof(&other, 500, &[a]));
+    }
+
+    #[test]
+    fn admission_entry_must_match_the_current_policy() {
+        let (dao_key, user, issuer) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut admission_dao = dao(100, 0);
+        admission_dao.admission_policy = AdmissionPolicy::Attestation;
+        admission_dao.admission_key = issuer;
+
+        let entry = AdmissionEntry { dao: dao_key, user, issuer, policy: AdmissionPolicy::Attestation };
+        assert!(entry.admits(&admission_dao, &dao_key, &user).is_ok());
+
+        admission_dao.admission_key = Pubkey::new_unique();
+        assert_eq!(entry.admits(&admission_dao, &dao_key, &user), Err(error::ErrorCode::NotAdmitted.into()));
+
+        admission_dao.admission_policy = AdmissionPolicy::Allowlist;
+        assert_eq!(entry.admits(&admission_dao, &dao_key, &user), Err(error::ErrorCode::NotAdmitted.into()));
+    }
+
+    fn metadata(mint: Pubkey, collection: Option<(bool, Pubkey)>) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(mint.as_ref());
+
+        for field in ["Quadra #1", "QDR", "https://quadra.test/1.json"] {
+            data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            data.extend_from_slice(field.as_bytes());
+        }
+
+        data.extend_from_slice(&500u16.to_le_bytes());
+
+        // a single verified creator with the whole share
+        data.push(1);
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(Pubkey::new_unique().as_ref());
+        data.extend_from_slice(&[1, 100]);
+
+        // primary sale happened, is mutable, an edition nonce and no token standard
+        data.extend_from_slice(&[1, 1, 1, 255, 0]);
+
+        match collection {
+            Some((verified, key)) => {
+                data.extend_from_slice(&[1, verified as u8]);
+                data.extend_from_slice(key.as_ref());
+            }
+            None => data.push(0),
+        }
+
+        // fields added by later metadata versions are ignored
+        data.extend_from_slice(&[0; 16]);
+
+        data
+    }
+
+    #[test]
+    fn metadata_collection_is_read_only_when_verified() {
+        let (mint, collection) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert_eq!(
+            read_metadata_collection(&metadata(mint, Some((true, collection)))),
+            Some((mint, Some(collection)))
+        );
+        assert_eq!(read_metadata_collection(&metadata(mint, Some((false, collection)))), Some((mint, None)));
+        assert_eq!(read_metadata_collection(&metadata(mint, None)), Some((mint, None)));
+
+        let data = metadata(mint, Some((true, collection)));
+        assert_eq!(read_metadata_collection(&data[..100]), None);
+    }
+
+    #[test]
+    fn nft_admits_a_single_wallet() {
+        let (dao, mint, user) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut claim = AdmissionClaim { dao: Pubkey::default(), mint: Pubkey::default(), user: Pubkey::default() };
+
+        assert!(claim.claim(&dao, &mint, &user).is_ok());
+        assert!(claim.claim(&dao, &mint, &user).is_ok());
+        assert_eq!(
+            claim.claim(&dao, &mint, &Pubkey::new_unique()),
+            Err(error::ErrorCode::AdmissionAlreadyClaimed.into())
+        );
+        assert_eq!(claim.user, user);
+    }
+
+    #[test]
+    fn apply_config_rejects_invalid_settings() {
+        let mut config_dao = dao(100, 0);
+
+        let defaults = DaoConfigArgs::default;
+        let invalid = [
+            (DaoConfigArgs { name: Some(String::from("Q")), ..defaults() }, error::ErrorCode::NameTooShort),
+            (DaoConfigArgs { membership_fee: Some(0), ..defaults() }, error::ErrorCode::InvalidFee),
+            (DaoConfigArgs { proposal_creation_fee: Some(0), ..defaults() }, error::ErrorCode::InvalidFee),
+            (
+                DaoConfigArgs { leave_refund_bps: Some(MAX_BASIS_POINTS + 1), ..defaults() },
+                error::ErrorCode::InvalidBasisPoints,
+            ),
+            (DaoConfigArgs { execution_delay: Some(-1), ..defaults() }, error::ErrorCode::InvalidExecutionDelay),
+            (
+                DaoConfigArgs {
+                    quorum_mode: Some(QuorumMode::ActiveMemberPercent),
+                    quorum: Some(MAX_BASIS_POINTS as u64 + 1),
+                    ..defaults()
+                },
+                error::ErrorCode::InvalidBasisPoints,
+            ),
+            (
+                DaoConfigArgs { approval_threshold_bps: Some(MAX_BASIS_POINTS + 1), ..defaults() },
+                error::ErrorCode::InvalidBasisPoints,
+            ),
+            (
+                DaoConfigArgs {
+                    credit_mode: Some(CreditMode::PerEpoch),
+                    credit_allowance: Some(100),
+                    ..defaults()
+                },
+                error::ErrorCode::InvalidCreditConfig,
+            ),
+            (
+                DaoConfigArgs { admission_policy: Some(AdmissionPolicy::MerkleAllowlist), ..defaults() },
+                error::ErrorCode::InvalidAdmissionPolicy,
+            ),
+            (
+                DaoConfigArgs { admission_policy: Some(AdmissionPolicy::CollectionHolding), ..defaults() },
+                error::ErrorCode::InvalidAdmissionPolicy,
+            ),
+        ];
+
+        for (args, code) in invalid {
+            assert_eq!(config_dao.apply_config(&args), Err(code.into()));
+        }
+
+        assert_eq!(config_dao.membership_fee, 100);
+        assert_eq!(config_dao.leave_refund_bps, 0);
+    }
+
+    #[test]
+    fn enums_keep_the_legacy_byte_layout() {
+        let mut current = proposal(3, 1);
+        current.status = ProposalStatus::Failed;
+        current.action = ProposalAction::Transfer;
+
+        let legacy = LegacyProposal::deserialize(&mut &current.try_to_vec().unwrap()[..]).unwrap();
+
+        assert_eq!(legacy.status, 2);
+        assert_eq!(legacy.action, 1);
+        assert_eq!(legacy.yes_votes, 3);
+        assert_eq!(legacy.end_date, current.end_date);
+    }
+
+    #[test]
+    fn migrate_maps_legacy_status_and_action() {
+        let mut legacy = proposal(3, 1);
+        legacy.status = ProposalStatus::Passed;
+        legacy.action = ProposalAction::Transfer;
+        legacy.version = 0;
+
+        let bytes = legacy.try_to_vec().unwrap();
+        let migrated = LegacyProposal::deserialize(&mut &bytes[..]).unwrap().migrate().unwrap();
+
+        assert_eq!(migrated.status, ProposalStatus::Passed);
+        assert_eq!(migrated.action, ProposalAction::Transfer);
+        assert_eq!(migrated.version, PROPOSAL_VERSION);
+    }
+
+    #[test]
+    fn migrate_cancels_active_proposals_with_unknown_actions() {
+        let mut bytes = proposal(0, 0).try_to_vec().unwrap();
+        // the action byte follows the title, description, tallies, token amount and status
+        let action_offset = 3 * 32 + 4 + 4 + 3 * 8 + 1;
+        bytes[action_offset] = 7;
+
+        let migrated = LegacyProposal::deserialize(&mut &bytes[..]).unwrap().migrate().unwrap();
+
+        assert_eq!(migrated.status, ProposalStatus::Cancelled);
+    }
+
+    #[test]
+    fn migrate_dao_keeps_legacy_settings_and_fills_defaults() {
+        let creator = Pubkey::new_unique();
+        let mut bytes = (
+            creator,
+            String::from("Quadratus DAO"),
+            String::new(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            4u64,
+            10u64,
+            5u64,
+            100u64,
+        )
+            .try_to_vec()
+            .unwrap();
+        // legacy accounts were allocated with zeroed room to spare
+        bytes.extend([0; 256]);
+
+        // the zeroed padding reads as an unmigrated current layout
+        assert_eq!(DAO::deserialize(&mut &bytes[..]).unwrap().version, 0);
+
+        let governance_mint = Pubkey::new_unique();
+        let migrated = LegacyDao::deserialize(&mut &bytes[..]).unwrap().migrate(governance_mint, 7);
+
+        assert_eq!(migrated.creator, creator);
+        assert_eq!(migrated.name, "Quadratus DAO");
+        assert_eq!(migrated.total_proposals, 4);
+        assert_eq!(migrated.min_yes_votes, 10);
+        assert_eq!(migrated.membership_fee, 100);
+        assert_eq!(migrated.admin_count, 1);
+        assert_eq!(migrated.member_count, 7);
+        assert_eq!(migrated.admission_policy, AdmissionPolicy::Open);
+        assert_eq!(migrated.governance_mint, governance_mint);
+        assert_eq!(migrated.version, DAO_VERSION);
+    }
+
+    #[test]
+    fn members_joined_before_the_counter_can_leave() {
+        let mut member_dao = dao(100, 0);
+
+        // a legacy member leaving a DAO migrated with too low a member count
+        member_dao.remove_member();
+        assert_eq!(member_dao.member_count, 0);
+
+        member_dao.add_member().unwrap();
+        member_dao.add_member().unwrap();
+        member_dao.remove_member();
+        assert_eq!(member_dao.member_count, 1);
+    }
+
+    #[test]
+    fn legacy_vote_records_migrate_to_the_current_layout() {
+        let voter = Pubkey::new_unique();
+        let amount_only = |amount: u64| LegacyUserProposalVotes::read(&amount.to_le_bytes()).unwrap();
+
+        let migrated = amount_only(3).migrate(voter, &proposal(5, 0)).unwrap();
+        assert_eq!((migrated.amount, migrated.tokens_spent, migrated.side), (3, 0, VoteSide::Yes));
+        assert_eq!(migrated.payer, voter);
+
+        let migrated = amount_only(0).migrate(voter, &proposal(5, 4)).unwrap();
+        assert_eq!(migrated.payer, Pubkey::default());
+
+        assert_eq!(
+            amount_only(3).migrate(voter, &proposal(5, 4)).map(|votes| votes.amount),
+            Err(error::ErrorCode::UnknownVoteSide.into())
+        );
+
+        let mut sided = (4u64, 16u64, VoteSide::No).try_to_vec().unwrap();
+        // the record was allocated with the padding of the struct
+        sided.resize(24, 0);
+
+        let migrated = LegacyUserProposalVotes::read(&sided)
+            .unwrap()
+            .migrate(voter, &proposal(0, 4))
+            .unwrap();
+        assert_eq!((migrated.amount, migrated.tokens_spent, migrated.side), (4, 16, VoteSide::No));
+        assert_eq!(migrated.payer, voter);
+
+        assert!(LegacyUserProposalVotes::read(&[0; 56]).is_err());
+    }
+
+    fn overflow() -> Error {
+        error::ErrorCode::MathOverflow.into()
+    }
+
+    proptest! {
+        #[test]
+        fn leave_refund_never_exceeds_membership_fee(
+            membership_fee in any::<u64>(),
+            leave_refund_bps in 0..=MAX_BASIS_POINTS
+        ) {
+            let refund = dao(membership_fee, leave_refund_bps).leave_refund().unwrap();
+
+            prop_assert!(refund <= membership_fee);
+            if leave_refund_bps == MAX_BASIS_POINTS {
+                prop_assert_eq!(refund, membership_fee);
+            }
+        }
+
+        #[test]
+        fn approval_matches_threshold_share(
+            yes_votes in any::<u64>(),
+            no_votes in any::<u64>(),
+            approval_threshold_bps in 0..=MAX_BASIS_POINTS
+        ) {
+            let mut threshold_dao = dao(100, 0);
+            threshold_dao.approval_threshold_bps = approval_threshold_bps;
+
+            let share = (yes_votes as f64) / ((yes_votes as f64) + (no_votes as f64));
+            let threshold = (approval_threshold_bps as f64) / (MAX_BASIS_POINTS as f64);
+            let approved = threshold_dao.approved(&proposal(yes_votes, no_votes));
+
+            prop_assert!(!approved || yes_votes > no_votes);
+            // away from the boundary the integer check agrees with the real share
+            if yes_votes > no_votes && (share - threshold).abs() > 1e-9 {
+                prop_assert_eq!(approved, share > threshold);
+            }
+        }
+
+        #[test]
+        fn vote_cost_matches_difference_of_squares(
+            previous in any::<u64>(),
+            amount in any::<u64>(),
+            decimals in any::<u8>()
+        ) {
+            let expected = (previous as u128)
+                .checked_add(amount as u128)
+                .and_then(|total| total.checked_mul(total))
+                .and_then(|total| total.checked_sub((previous as u128).checked_mul(previous as u128)?))
+                .and_then(|cost| cost.checked_mul((10u128).checked_pow(decimals as u32)?))
+                .and_then(|cost| u64::try_from(cost).ok());
+
+            match votes(previous).vote_cost(amount, decimals) {
+                Ok(cost) => prop_assert_eq!(Some(cost), expected),
+                Err(err) => {
+                    prop_assert_eq!(expected, None);
+                    prop_assert_eq!(err, overflow());
+                }
+            }
+        }
+
+        #[test]
+        fn tokens_spent_is_square_of_votes(
+            amounts in proptest::collection::vec(any::<u64>(), 1..20),
+            decimals in any::<u8>()
+        ) {
+            let mut user_votes = votes(0);
+            let scale = (10u128).checked_pow(decimals as u32);
+            let spent_for = |amount: u128| scale.and_then(|scale| amount.checked_mul(amount)?.checked_mul(scale));
+
+            for amount in amounts {
+                let total = (user_votes.amount as u128) + (amount as u128);
+                let result = match user_votes.vote_cost(amount, decimals) {
+                    Ok(cost) => user_votes.add_votes(VoteSide::Yes, amount, cost),
+                    Err(err) => Err(err),
+                };
+
+                if let Err(err) = result {
+                    // the vote only fails once the squared total no longer fits in the token supply
+                    prop_assert!(!matches!(spent_for(total), Some(spent) if spent <= (u64::MAX as u128)));
+                    prop_assert_eq!(err, overflow());
+                    break;
+                }
+            }
+
+            let spent = spent_for(user_votes.amount as u128).unwrap_or_default();
+            prop_assert_eq!(user_votes.tokens_spent as u128, spent);
+        }
+
+        #[test]
+        fn add_votes_never_wraps(
+            amount in any::<u64>(),
+            tokens_spent in any::<u64>(),
+            added in any::<u64>(),
+            cost in any::<u64>()
+        ) {
+            let mut user_votes = UserProposalVotes {
+                amount,
+                tokens_spent,
+                side: VoteSide::No,
+                payer: Pubkey::default(),
+            };
+
+            match user_votes.add_votes(VoteSide::Yes, added, cost) {
+                Ok(()) => {
+                    prop_assert_eq!(user_votes.amount as u128, (amount as u128) + (added as u128));
+                    prop_assert_eq!(
+                        user_votes.tokens_spent as u128,
+                        (tokens_spent as u128) + (cost as u128)
+                    );
+                }
+                Err(err) => {
+                    prop_assert!(amount.checked_add(added).is_none() || tokens_spent.checked_add(cost).is_none());
+                    prop_assert_eq!(err, overflow());
+                }
+            }
+        }
+
+        #[test]
+        fn option_votes_cost_the_sum_of_squares(
+            option_votes in proptest::collection::vec((0u8..4, 0u64..1_000), 0..20),
+            decimals in 0u8..9
+        ) {
+            let mut user_votes = UserOptionVotes { votes: vec![0; 4], tokens_spent: 0 };
+
+            for (option, amount) in option_votes {
+                let cost = user_votes.vote_cost(option, amount, decimals).unwrap();
+                user_votes.add_votes(option, amount, cost).unwrap();
+            }
+
+            let squares: u64 = user_votes.votes.iter().map(|votes| votes * votes).sum();
+            prop_assert_eq!(user_votes.tokens_spent, squares * (10u64).pow(decimals as u32));
+        }
+
+        #[test]
+        fn stake_weight_stays_within_the_boost(
+            amount in any::<u64>(),
+            lockup_end in any::<i64>(),
+            now in any::<i64>(),
+            lockup_boost_bps in 0..=MAX_BASIS_POINTS
+        ) {
+            let max_weight = (amount as u128) * ((MAX_BASIS_POINTS + lockup_boost_bps) as u128) /
+                (MAX_BASIS_POINTS as u128);
+
+            match stake(amount, lockup_end).voting_weight(lockup_boost_bps, now) {
+                Ok(weight) => {
+                    prop_assert!(weight >= amount);
+                    prop_assert!((weight as u128) <= max_weight);
+                }
+                Err(err) => {
+                    prop_assert!(max_weight > (u64::MAX as u128));
+                    prop_assert_eq!(err, overflow());
+                }
+            }
+        }
+
+        #[test]
+        fn proposal_tallies_never_wrap(
+            yes_votes in any::<u64>(),
+            no_votes in any::<u64>(),
+            side in prop_oneof![Just(VoteSide::No), Just(VoteSide::Yes), Just(VoteSide::Abstain)],
+            amount in any::<u64>()
+        ) {
+            let tally_of = |tallies: &Proposal| match side {
+                VoteSide::No => tallies.no_votes,
+                VoteSide::Yes => tallies.yes_votes,
+                VoteSide::Abstain => tallies.abstain_votes,
+            };
+
+            let mut tallies = proposal(yes_votes, no_votes);
+            tallies.abstain_votes = yes_votes;
+            let tally = tally_of(&tallies);
+
+            match tallies.add_votes(side, amount) {
+                Ok(()) => {
+                    let updated = tally_of(&tallies);
+                    prop_assert_eq!(updated as u128, (tally as u128) + (amount as u128));
+                }
+                Err(err) => {
+                    prop_assert!(tally.checked_add(amount).is_none());
+                    prop_assert_eq!(err, overflow());
+                }
+            }
+
+            let mut tallies = proposal(yes_votes, no_votes);
+            tallies.abstain_votes = yes_votes;
+
+            match tallies.remove_votes(side, amount) {
+                Ok(()) => {
+                    let updated = tally_of(&tallies);
+                    prop_assert_eq!(updated, tally - amount);
+                }
+                Err(err) => {
+                    prop_assert!(amount > tally);
+                    prop_assert_eq!(err, overflow());
+                }
+            }
+        }
+    }
 }
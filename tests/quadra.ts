@@ -8,13 +8,19 @@ import {
   ASSOCIATED_TOKEN_PROGRAM_ID,
   getOrCreateAssociatedTokenAccount,
   mintTo,
+  transfer,
   TOKEN_PROGRAM_ID,
 } from "@solana/spl-token";
 import { Connection, LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import { keccak_256 } from "@noble/hashes/sha3";
 import assert from "assert";
 
 const DECIMALS_PER_TOKEN = 1000000;
 
+const METADATA_PROGRAM_ID = new PublicKey(
+  "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s"
+);
+
 const RPC_URL = "http://127.0.0.1:8899";
 
 // mute on devnet
@@ -78,8 +84,18 @@ describe("quadra", () => {
     program.programId
   );
 
+  const [registryPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("creator_registry"), payer.publicKey.toBuffer()],
+    program.programId
+  );
+
+  // first DAO created by the payer
   const [daoPDA] = anchor.web3.PublicKey.findProgramAddressSync(
-    [Buffer.from("dao"), payer.publicKey.toBuffer()],
+    [
+      Buffer.from("dao"),
+      payer.publicKey.toBuffer(),
+      new anchor.BN(0).toBuffer("le", 8),
+    ],
     program.programId
   );
 
@@ -93,6 +109,11 @@ describe("quadra", () => {
     program.programId
   );
 
+  const [daoAuthorityPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("dao_authority"), daoPDA.toBuffer()],
+    program.programId
+  );
+
   const [burnPDA] = anchor.web3.PublicKey.findProgramAddressSync(
     [Buffer.from("burn"), daoPDA.toBuffer()],
     program.programId
@@ -116,6 +137,11 @@ describe("quadra", () => {
     program.programId
   );
 
+  const [escrowPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("escrow_vault"), proposalPDA.toBuffer()],
+    program.programId
+  );
+
   const [userProposalVotesPDA] = anchor.web3.PublicKey.findProgramAddressSync(
     [
       Buffer.from("user_proposal_votes"),
@@ -125,6 +151,492 @@ describe("quadra", () => {
     program.programId
   );
 
+  const sleep = (ms: number) =>
+    new Promise((resolve) => setTimeout(resolve, ms));
+
+  const membershipAddress = (user: PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("membership"), daoPDA.toBuffer(), user.toBuffer()],
+      program.programId
+    )[0];
+
+  const userVotesAddress = (voter: PublicKey, proposal: PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("user_proposal_votes"),
+        voter.toBuffer(),
+        proposal.toBuffer(),
+      ],
+      program.programId
+    )[0];
+
+  const tokenAccount = (owner: PublicKey) =>
+    getAssociatedTokenAddress(governanceMintKeypair.publicKey, owner);
+
+  const tokenSupply = async () =>
+    Number(
+      (await connection.getTokenSupply(governanceMintKeypair.publicKey)).value
+        .amount
+    );
+
+  const tokenBalance = async (account: PublicKey) =>
+    Number((await connection.getTokenAccountBalance(account)).value.amount);
+
+  // DAO config update that leaves every setting not overridden untouched
+  const configArgs = (overrides = {}) => ({
+    name: null,
+    image: null,
+    minYesVotes: null,
+    proposalCreationFee: null,
+    membershipFee: null,
+    leaveRefundBps: null,
+    executionDelay: null,
+    executionWindow: null,
+    quorumMode: null,
+    quorum: null,
+    approvalThresholdBps: null,
+    actionThresholdBps: null,
+    largeTransferAmount: null,
+    largeTransferThresholdBps: null,
+    creditMode: null,
+    creditAllowance: null,
+    creditEpochLength: null,
+    creditChargeTokens: null,
+    admissionPolicy: null,
+    admissionRoot: null,
+    admissionKey: null,
+    lockupBoostBps: null,
+    ...overrides,
+  });
+
+  const updateDaoConfig = (overrides = {}) =>
+    program.methods
+      .updateDaoConfig(configArgs(overrides))
+      .accounts({
+        dao: daoPDA,
+        admin: adminPDA,
+        user: payer.publicKey,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+  // addresses of the next proposal of the DAO
+  const nextProposal = async () => {
+    const dao = await program.account.dao.fetch(daoPDA);
+
+    const [proposal] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("proposal"),
+        daoPDA.toBuffer(),
+        dao.totalProposals.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+
+    const [escrow] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("escrow_vault"), proposal.toBuffer()],
+      program.programId
+    );
+
+    return { proposal, escrow };
+  };
+
+  // proposal of the second payer that ends a few seconds from now
+  const createProposalMethod = async (args = {}, accounts = {}) => {
+    const { proposal, escrow } = await nextProposal();
+
+    return program.methods
+      .createProposal({
+        tokenAmount: new anchor.BN(0),
+        endDate: new anchor.BN(Math.floor(Date.now() / 1000) + 5),
+        title: "Short lived proposal.",
+        description: "Proposal that ends a few seconds after it is created.",
+        action: { transfer: {} },
+        burnOnVote: false,
+        snapshotRoot: Array(32).fill(0),
+        config: null,
+        ...args,
+      })
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        configChange: null,
+        treasuryVault: treasuryPDA,
+        escrowVault: escrow,
+        beneficiary: await tokenAccount(beneficiary.publicKey),
+        beneficiaryOwner: beneficiary.publicKey,
+        membership: membershipPDA,
+        admin: null,
+        user: secondPayer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        userTokenMintAccount: await tokenAccount(secondPayer.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        ...accounts,
+      })
+      .signers([secondPayer]);
+  };
+
+  const createProposal = async (args = {}, accounts = {}) => {
+    const addresses = await nextProposal();
+
+    await (await createProposalMethod(args, accounts))
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    return addresses;
+  };
+
+  const voteOnProposal = async (
+    proposal: PublicKey,
+    escrow: PublicKey,
+    amount: number,
+    side = { yes: {} },
+    voter = secondPayer
+  ) =>
+    program.methods
+      .voteOnProposal(new anchor.BN(amount), side)
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        userProposalVotes: userVotesAddress(voter.publicKey, proposal),
+        escrowVault: escrow,
+        userTokenMintAccount: await tokenAccount(voter.publicKey),
+        membership: membershipAddress(voter.publicKey),
+        voiceCredits: null,
+        user: voter.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([voter])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+  // waits until the cluster clock has passed the end date of the proposal
+  const waitForEnd = async (proposal: PublicKey) => {
+    const { endDate } = await program.account.proposal.fetch(proposal);
+
+    while (
+      (await connection.getBlockTime(await connection.getSlot())) <=
+      endDate.toNumber()
+    ) {
+      await sleep(1000);
+    }
+  };
+
+  const finalizeProposal = (proposal: PublicKey, accounts = {}) =>
+    program.methods
+      .finalizeProposal()
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        options: null,
+        ...accounts,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+  const executeProposal = (proposal: PublicKey, accounts = {}) =>
+    program.methods
+      .executeProposal()
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        treasuryVault: treasuryPDA,
+        beneficiary: null,
+        configChange: null,
+        user: payer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        ...accounts,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+  const proposalInstructionAddress = (proposal: PublicKey, index: number) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("proposal_instruction"), proposal.toBuffer(), Buffer.from([index])],
+      program.programId
+    )[0];
+
+  // appends an instruction to an execute instructions proposal of the second payer
+  const insertProposalInstruction = (
+    proposal: PublicKey,
+    index: number,
+    instruction: anchor.web3.TransactionInstruction
+  ) =>
+    program.methods
+      .insertProposalInstruction(
+        instruction.programId,
+        instruction.keys.map((key) => ({
+          pubkey: key.pubkey,
+          isSigner: key.isSigner,
+          isWritable: key.isWritable,
+        })),
+        instruction.data
+      )
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        proposalInstruction: proposalInstructionAddress(proposal, index),
+        user: secondPayer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer]);
+
+  const executeProposalInstruction = (
+    proposal: PublicKey,
+    index: number,
+    instruction: anchor.web3.TransactionInstruction
+  ) =>
+    program.methods
+      .executeProposalInstruction()
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        proposalInstruction: proposalInstructionAddress(proposal, index),
+        treasuryVault: treasuryPDA,
+        daoAuthority: daoAuthorityPDA,
+        user: payer.publicKey,
+      })
+      .remainingAccounts([
+        ...instruction.keys.map((key) => ({
+          pubkey: key.pubkey,
+          isSigner: false,
+          isWritable: key.isWritable,
+        })),
+        {
+          pubkey: instruction.programId,
+          isSigner: false,
+          isWritable: false,
+        },
+      ]);
+
+  // cancels as the proposal creator, or as an admin veto when signed by the payer
+  const cancelProposal = (proposal: PublicKey, admin = false) =>
+    program.methods
+      .cancelProposal()
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        admin: admin ? adminPDA : null,
+        user: admin ? payer.publicKey : secondPayer.publicKey,
+      })
+      .signers(admin ? [] : [secondPayer]);
+
+  // wallet with SOL and enough governance tokens to pay the membership fee
+  const newMember = async () => {
+    const member = new anchor.web3.Keypair();
+
+    await connection.confirmTransaction(
+      await connection.requestAirdrop(member.publicKey, LAMPORTS_PER_SOL)
+    );
+
+    const memberTokenAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer.payer,
+      governanceMintKeypair.publicKey,
+      member.publicKey
+    );
+
+    await mintTo(
+      connection,
+      payer.payer,
+      governanceMintKeypair.publicKey,
+      memberTokenAccount.address,
+      payer.payer,
+      100 * DECIMALS_PER_TOKEN
+    );
+
+    return member;
+  };
+
+  const admissionEntryAddress = (user: PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("admission"), daoPDA.toBuffer(), user.toBuffer()],
+      program.programId
+    )[0];
+
+  const admissionClaimAddress = (mint: PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("admission_claim"), daoPDA.toBuffer(), mint.toBuffer()],
+      program.programId
+    )[0];
+
+  const joinDao = async (
+    member: anchor.web3.Keypair,
+    proof: number[][] = [],
+    accounts = {}
+  ) =>
+    program.methods
+      .joinDao(proof)
+      .accounts({
+        dao: daoPDA,
+        membership: membershipAddress(member.publicKey),
+        treasuryVault: treasuryPDA,
+        userTokenMintAccount: await tokenAccount(member.publicKey),
+        admissionEntry: null,
+        admissionTokenAccount: null,
+        admissionMetadata: null,
+        admissionClaim: null,
+        user: member.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+        ...accounts,
+      })
+      .signers([member])
+      .rpc();
+
+  const addAdmissionEntry = (
+    member: PublicKey,
+    issuer: anchor.web3.Keypair = payer.payer
+  ) =>
+    program.methods
+      .addAdmissionEntry()
+      .accounts({
+        dao: daoPDA,
+        admissionEntry: admissionEntryAddress(member),
+        member,
+        admin: issuer === payer.payer ? adminPDA : null,
+        user: issuer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers(issuer === payer.payer ? [] : [issuer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+  const isMember = async (member: PublicKey) =>
+    (await program.account.membership.fetchNullable(
+      membershipAddress(member)
+    )) !== null;
+
+  // mint with a single token held by the owner
+  const createNft = async (owner: PublicKey) => {
+    const mint = await createMint(
+      connection,
+      payer.payer,
+      payer.publicKey,
+      null,
+      0
+    );
+    const ownerAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer.payer,
+      mint,
+      owner
+    );
+
+    await mintTo(connection, payer.payer, mint, ownerAccount.address, payer.payer, 1);
+
+    return { mint, ownerAccount: ownerAccount.address };
+  };
+
+  const delegationAddress = (delegator: PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("delegation"), daoPDA.toBuffer(), delegator.toBuffer()],
+      program.programId
+    )[0];
+
+  const delegateMethod = (
+    delegator: anchor.web3.Keypair,
+    delegate: PublicKey
+  ) =>
+    program.methods
+      .delegate(delegate)
+      .accounts({
+        dao: daoPDA,
+        delegation: delegationAddress(delegator.publicKey),
+        membership: membershipAddress(delegator.publicKey),
+        delegateMembership: membershipAddress(delegate),
+        user: delegator.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([delegator]);
+
+  // stakes governance tokens of a member and returns a vote with the stake on the proposal
+  const stakeFor = async (
+    member: anchor.web3.Keypair,
+    amount: number,
+    lockupPeriod: number
+  ) => {
+    const [stakeAccount] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stake"), daoPDA.toBuffer(), member.publicKey.toBuffer()],
+      program.programId
+    );
+    const [stakeVault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("stake_vault"),
+        daoPDA.toBuffer(),
+        member.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .stake(new anchor.BN(amount), new anchor.BN(lockupPeriod))
+      .accounts({
+        dao: daoPDA,
+        stakeAccount,
+        stakeVault,
+        userTokenMintAccount: await tokenAccount(member.publicKey),
+        membership: membershipAddress(member.publicKey),
+        user: member.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([member])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    return (proposal: PublicKey, votes: number) =>
+      program.methods
+        .voteWithStake(new anchor.BN(votes), { yes: {} })
+        .accounts({
+          dao: daoPDA,
+          proposal,
+          stakeAccount,
+          userProposalVotes: userVotesAddress(member.publicKey, proposal),
+          membership: membershipAddress(member.publicKey),
+          voiceCredits: null,
+          user: member.publicKey,
+          tokenMint: governanceMintKeypair.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([member])
+        .rpc();
+  };
+
+  // lamport transfer signed by the DAO authority
+  const authorityTransfer = (recipient: PublicKey, lamports: number) =>
+    anchor.web3.SystemProgram.transfer({
+      fromPubkey: daoAuthorityPDA,
+      toPubkey: recipient,
+      lamports,
+    });
+
   it("airdrops SOL to the payer", async () => {
     // mute on devnet
     const lamports = LAMPORTS_PER_SOL;
@@ -199,6 +711,7 @@ describe("quadra", () => {
     const min_yes_votes = new anchor.BN(1000);
     const proposal_creation_fee = new anchor.BN(1 * DECIMALS_PER_TOKEN);
     const membership_fee = new anchor.BN(100 * DECIMALS_PER_TOKEN);
+    const leave_refund_bps = 5000; // half of the membership fee is refunded
 
     const mintAndSendTokens = async (
       mintPubKey: PublicKey,
@@ -238,11 +751,14 @@ describe("quadra", () => {
         image,
         min_yes_votes,
         proposal_creation_fee,
-        membership_fee
+        membership_fee,
+        leave_refund_bps
       )
       .accounts({
+        registry: registryPDA,
         dao: daoPDA,
         admin: adminPDA,
+        membership: membershipAddress(payer.publicKey),
         treasuryVault: treasuryPDA,
         burnVault: burnPDA,
         feeAccount: feePDA,
@@ -258,8 +774,121 @@ describe("quadra", () => {
       });
 
     const dao = await program.account.dao.fetch(daoPDA);
+    const registry = await program.account.creatorRegistry.fetch(registryPDA);
 
     assert.ok(dao);
+    assert.equal(dao.version, 1);
+    assert.ok(dao.governanceMint.equals(governanceMintKeypair.publicKey));
+    // the creator is the first member
+    assert.equal(dao.memberCount.toNumber(), 1);
+    assert.ok(registry.daos[0].equals(daoPDA));
+  });
+
+  it("refuses to migrate a DAO in the current layout", async () => {
+    const error = await program.methods
+      .migrateDao(new anchor.BN(1))
+      .accounts({
+        dao: daoPDA,
+        admin: adminPDA,
+        treasuryVault: treasuryPDA,
+        user: payer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "DaoAlreadyMigrated");
+  });
+
+  it("updates the DAO config", async () => {
+    const min_yes_votes = new anchor.BN(100);
+
+    await program.methods
+      .updateDaoConfig({
+        name: null,
+        image: null,
+        minYesVotes: min_yes_votes,
+        proposalCreationFee: null,
+        membershipFee: null,
+        leaveRefundBps: null,
+        executionDelay: null,
+        executionWindow: null,
+        quorumMode: { distinctVoters: {} },
+        quorum: new anchor.BN(1),
+        approvalThresholdBps: null,
+        actionThresholdBps: null,
+        largeTransferAmount: null,
+        largeTransferThresholdBps: null,
+        creditMode: null,
+        creditAllowance: null,
+        creditEpochLength: null,
+        creditChargeTokens: null,
+        admissionPolicy: null,
+        admissionRoot: null,
+        admissionKey: null,
+        lockupBoostBps: null,
+      })
+      .accounts({
+        dao: daoPDA,
+        admin: adminPDA,
+        user: payer.publicKey,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const dao = await program.account.dao.fetch(daoPDA);
+
+    assert.ok(dao.minYesVotes.eq(min_yes_votes));
+    assert.equal(dao.name, "Quadratus DAO");
+  });
+
+  it("adds and removes an admin", async () => {
+    const secondAdmin = new anchor.web3.Keypair();
+
+    const [secondAdminPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("admin"), daoPDA.toBuffer(), secondAdmin.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await program.methods
+      .addAdmin()
+      .accounts({
+        dao: daoPDA,
+        admin: adminPDA,
+        newAdmin: secondAdminPDA,
+        newAdminUser: secondAdmin.publicKey,
+        user: payer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const daoWithTwoAdmins = await program.account.dao.fetch(daoPDA);
+
+    assert.equal(daoWithTwoAdmins.adminCount, 2);
+
+    await program.methods
+      .removeAdmin()
+      .accounts({
+        dao: daoPDA,
+        admin: adminPDA,
+        removedAdmin: secondAdminPDA,
+        removedAdminUser: secondAdmin.publicKey,
+        user: payer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const daoWithOneAdmin = await program.account.dao.fetch(daoPDA);
+
+    assert.equal(daoWithOneAdmin.adminCount, 1);
   });
 
   it("joins the DAO", async () => {
@@ -271,12 +900,16 @@ describe("quadra", () => {
     );
 
     await program.methods
-      .joinDao()
+      .joinDao([])
       .accounts({
         dao: daoPDA,
         membership: membershipPDA,
         treasuryVault: treasuryPDA,
         userTokenMintAccount: userTokenMintAccount.address,
+        admissionEntry: null,
+        admissionTokenAccount: null,
+        admissionMetadata: null,
+        admissionClaim: null,
         user: secondPayer.publicKey,
         tokenMint: governanceMintKeypair.publicKey,
         tokenProgram: TOKEN_PROGRAM_ID,
@@ -292,18 +925,135 @@ describe("quadra", () => {
     const daoAfter = await program.account.dao.fetch(daoPDA);
 
     assert.ok(daoAfter);
+    assert.equal(daoAfter.memberCount.toNumber(), 2);
   });
 
-  it("creates the proposal", async () => {
-    const token_amount = new anchor.BN(1 * DECIMALS_PER_TOKEN); // TODO: check for decimals
-    const end_date_in_seconds = Date.now() / 1000 + 60 * 60 * 24 * 3;
-    const end_date = new anchor.BN(end_date_in_seconds);
-    const title = "Allocate tokens for airdrop.";
-    const description = `This proposal outlines a strategic plan to allocate a portion of our DAO's token reserves towards a targeted airdrop program.\n The primary goal is to incentivize participation, attract new members, and stimulate community engagement. By distributing tokens directly to the wallets of active and potential community members, we aim to enhance the decentralized governance model of our DAO and reward those who contribute meaningfully to its growth.`;
-    const action = 1; // 0 = burn, 1 = transfer
-    const burnOnVote = false;
+  it("expels and reactivates a member", async () => {
+    const moderateAccounts = {
+      dao: daoPDA,
+      membership: membershipPDA,
+      member: secondPayer.publicKey,
+      admin: adminPDA,
+      user: payer.publicKey,
+    };
 
-    const userTokenMintAccount = await getOrCreateAssociatedTokenAccount(
+    await program.methods
+      .expelMember()
+      .accounts(moderateAccounts)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const expelled = await program.account.membership.fetch(membershipPDA);
+
+    assert.equal(expelled.active, false);
+    assert.equal(
+      (await program.account.dao.fetch(daoPDA)).memberCount.toNumber(),
+      1
+    );
+
+    await program.methods
+      .reactivateMember()
+      .accounts(moderateAccounts)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const reactivated = await program.account.membership.fetch(membershipPDA);
+
+    assert.equal(reactivated.active, true);
+    assert.equal(
+      (await program.account.dao.fetch(daoPDA)).memberCount.toNumber(),
+      2
+    );
+  });
+
+  const [choiceProposalPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("proposal"),
+      daoPDA.toBuffer(),
+      new anchor.BN(1).toBuffer("le", 8),
+    ],
+    program.programId
+  );
+
+  const [choiceEscrowPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("escrow_vault"), choiceProposalPDA.toBuffer()],
+    program.programId
+  );
+
+  const [optionsPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("proposal_options"), choiceProposalPDA.toBuffer()],
+    program.programId
+  );
+
+  const [userOptionVotesPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("user_option_votes"),
+      secondPayer.publicKey.toBuffer(),
+      choiceProposalPDA.toBuffer(),
+    ],
+    program.programId
+  );
+
+  it("delegates and undelegates", async () => {
+    const [delegationPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("delegation"),
+        daoPDA.toBuffer(),
+        secondPayer.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+
+    await program.methods
+      .delegate(payer.publicKey)
+      .accounts({
+        dao: daoPDA,
+        delegation: delegationPDA,
+        membership: membershipPDA,
+        delegateMembership: membershipAddress(payer.publicKey),
+        user: secondPayer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const delegation = await program.account.delegation.fetch(delegationPDA);
+
+    assert.ok(delegation.delegate.equals(payer.publicKey));
+
+    await program.methods
+      .undelegate()
+      .accounts({
+        dao: daoPDA,
+        delegation: delegationPDA,
+        user: secondPayer.publicKey,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    assert.equal(await connection.getAccountInfo(delegationPDA), null);
+  });
+
+  it("creates the proposal", async () => {
+    const token_amount = new anchor.BN(1 * DECIMALS_PER_TOKEN); // TODO: check for decimals
+    const end_date_in_seconds = Date.now() / 1000 + 60 * 60 * 24 * 3;
+    const end_date = new anchor.BN(end_date_in_seconds);
+    const title = "Allocate tokens for airdrop.";
+    const description = `This proposal outlines a strategic plan to allocate a portion of our DAO's token reserves towards a targeted airdrop program.\n The primary goal is to incentivize participation, attract new members, and stimulate community engagement. By distributing tokens directly to the wallets of active and potential community members, we aim to enhance the decentralized governance model of our DAO and reward those who contribute meaningfully to its growth.`;
+    const action = { transfer: {} };
+    const burnOnVote = false;
+
+    const userTokenMintAccount = await getOrCreateAssociatedTokenAccount(
       connection,
       payer.payer,
       governanceMintKeypair.publicKey,
@@ -323,21 +1073,26 @@ describe("quadra", () => {
     );
 
     await program.methods
-      .createProposal(
-        token_amount,
-        end_date,
+      .createProposal({
+        tokenAmount: token_amount,
+        endDate: end_date,
         title,
         description,
         action,
-        burnOnVote
-      )
+        burnOnVote,
+        snapshotRoot: Array(32).fill(0), // no snapshot, members vote
+        config: null,
+      })
       .accounts({
         dao: daoPDA,
         proposal: proposalPDA,
+        configChange: null,
         treasuryVault: treasuryPDA,
+        escrowVault: escrowPDA,
         beneficiary: beneficiary_ata,
         beneficiaryOwner: beneficiary.publicKey,
         membership: membershipPDA,
+        admin: null,
         user: secondPayer.publicKey,
         tokenMint: governanceMintKeypair.publicKey,
         userTokenMintAccount: SecondUserTokenMintAccount.address,
@@ -383,7 +1138,7 @@ describe("quadra", () => {
     );
 
     const amount = new anchor.BN(100); // amount of votes
-    const side = 1; // 1 for yes, 0 for no
+    const side = { yes: {} };
 
     await program.methods
       .voteOnProposal(amount, side)
@@ -391,10 +1146,10 @@ describe("quadra", () => {
         dao: daoPDA,
         proposal: proposalPDA,
         userProposalVotes: userProposalVotesPDA,
-        treasuryVault: treasuryPDA,
-        burnVault: burnPDA,
+        escrowVault: escrowPDA,
         userTokenMintAccount: SecondUserTokenMintAccount.address,
         membership: membershipPDA,
+        voiceCredits: null,
         user: secondPayer.publicKey,
         tokenMint: governanceMintKeypair.publicKey,
         tokenProgram: TOKEN_PROGRAM_ID,
@@ -406,12 +1161,86 @@ describe("quadra", () => {
       .catch((e) => {
         console.log(e);
       });
+
+    const userProposalVotes = await program.account.userProposalVotes.fetch(
+      userProposalVotesPDA
+    );
+
+    // the total spend is always the square of the vote count
+    assert.ok(
+      userProposalVotes.tokensSpent.eq(
+        userProposalVotes.amount
+          .mul(userProposalVotes.amount)
+          .mul(new anchor.BN(DECIMALS_PER_TOKEN))
+      )
+    );
   });
 
-  it("executes a completed proposal", async () => {
+  it("switches the vote side", async () => {
+    const SecondUserTokenMintAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer.payer,
+      governanceMintKeypair.publicKey,
+      secondPayer.publicKey
+    );
+
+    const switchAccounts = {
+      dao: daoPDA,
+      proposal: proposalPDA,
+      userProposalVotes: userProposalVotesPDA,
+      escrowVault: escrowPDA,
+      userTokenMintAccount: SecondUserTokenMintAccount.address,
+      membership: membershipPDA,
+      voiceCredits: null,
+      user: secondPayer.publicKey,
+      tokenMint: governanceMintKeypair.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+
+    // move the votes to no, then back to yes without adding new votes
+    for (const side of [{ no: {} }, { yes: {} }]) {
+      await program.methods
+        .switchVoteSide(side, new anchor.BN(0))
+        .accounts(switchAccounts)
+        .signers([secondPayer])
+        .rpc()
+        .catch((e) => {
+          console.log(e);
+        });
+    }
+
+    const userProposalVotes = await program.account.userProposalVotes.fetch(
+      userProposalVotesPDA
+    );
+
+    assert.deepEqual(userProposalVotes.side, { yes: {} });
+  });
+
+  it("finalizes a completed proposal", async () => {
     // wait 1 minutes
     // await new Promise((resolve) => setTimeout(resolve, 60000));
 
+    await program.methods
+      .finalizeProposal()
+      .accounts({
+        dao: daoPDA,
+        proposal: proposalPDA,
+        options: null,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const proposal = await program.account.proposal.fetch(proposalPDA);
+
+    assert.deepEqual(proposal.status, { passed: {} });
+    assert.equal(proposal.voterCount.toNumber(), 1);
+  });
+
+  it("executes a completed proposal", async () => {
     const beneficiary_ata = await getAssociatedTokenAddress(
       governanceMintKeypair.publicKey,
       beneficiary.publicKey
@@ -423,9 +1252,8 @@ describe("quadra", () => {
         dao: daoPDA,
         proposal: proposalPDA,
         treasuryVault: treasuryPDA,
-        burnVault: burnPDA,
         beneficiary: beneficiary_ata,
-        beneficiaryOwner: beneficiary.publicKey,
+        configChange: null,
         user: payer.publicKey,
         tokenMint: governanceMintKeypair.publicKey,
         tokenProgram: TOKEN_PROGRAM_ID,
@@ -448,4 +1276,1118 @@ describe("quadra", () => {
 
     assert.ok(proposal.executed);
   });
+
+  it("withdraws the vote deposit", async () => {
+    const SecondUserTokenMintAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer.payer,
+      governanceMintKeypair.publicKey,
+      secondPayer.publicKey
+    );
+
+    await program.methods
+      .withdrawVoteDeposit()
+      .accounts({
+        dao: daoPDA,
+        proposal: proposalPDA,
+        userProposalVotes: userProposalVotesPDA,
+        voter: secondPayer.publicKey,
+        escrowVault: escrowPDA,
+        userTokenMintAccount: SecondUserTokenMintAccount.address,
+        user: secondPayer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const userProposalVotes = await connection.getAccountInfo(
+      userProposalVotesPDA
+    );
+
+    assert.equal(userProposalVotes, null);
+  });
+
+  it("creates a choice proposal with options", async () => {
+    const end_date = new anchor.BN(Date.now() / 1000 + 60 * 60 * 24 * 3);
+    const labels = ["Grants", "Audits", "Marketing"];
+
+    const SecondUserTokenMintAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer.payer,
+      governanceMintKeypair.publicKey,
+      secondPayer.publicKey
+    );
+
+    const beneficiary_ata = await getAssociatedTokenAddress(
+      governanceMintKeypair.publicKey,
+      beneficiary.publicKey
+    );
+
+    await program.methods
+      .createProposal({
+        tokenAmount: new anchor.BN(0),
+        endDate: end_date,
+        title: "Prioritize the next quarter.",
+        description: "Pick the area the DAO should focus on next quarter.",
+        action: { choice: {} },
+        burnOnVote: false,
+        snapshotRoot: Array(32).fill(0),
+        config: null,
+      })
+      .accounts({
+        dao: daoPDA,
+        proposal: choiceProposalPDA,
+        configChange: null,
+        treasuryVault: treasuryPDA,
+        escrowVault: choiceEscrowPDA,
+        beneficiary: beneficiary_ata,
+        beneficiaryOwner: beneficiary.publicKey,
+        membership: membershipPDA,
+        admin: null,
+        user: secondPayer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        userTokenMintAccount: SecondUserTokenMintAccount.address,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    await program.methods
+      .setProposalOptions(labels)
+      .accounts({
+        dao: daoPDA,
+        proposal: choiceProposalPDA,
+        options: optionsPDA,
+        user: secondPayer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const options = await program.account.proposalOptions.fetch(optionsPDA);
+
+    assert.deepEqual(options.labels, labels);
+  });
+
+  it("votes on proposal options", async () => {
+    const SecondUserTokenMintAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer.payer,
+      governanceMintKeypair.publicKey,
+      secondPayer.publicKey
+    );
+
+    const voteOnOption = (option: number, amount: number) =>
+      program.methods
+        .voteOnOption(option, new anchor.BN(amount))
+        .accounts({
+          dao: daoPDA,
+          proposal: choiceProposalPDA,
+          options: optionsPDA,
+          userOptionVotes: userOptionVotesPDA,
+          escrowVault: choiceEscrowPDA,
+          userTokenMintAccount: SecondUserTokenMintAccount.address,
+          membership: membershipPDA,
+          voiceCredits: null,
+          user: secondPayer.publicKey,
+          tokenMint: governanceMintKeypair.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([secondPayer])
+        .rpc()
+        .catch((e) => {
+          console.log(e);
+        });
+
+    await voteOnOption(0, 3);
+    await voteOnOption(2, 4);
+
+    const userOptionVotes = await program.account.userOptionVotes.fetch(
+      userOptionVotesPDA
+    );
+
+    // each option is priced on its own, 3^2 + 4^2
+    assert.ok(
+      userOptionVotes.tokensSpent.eq(new anchor.BN(25 * DECIMALS_PER_TOKEN))
+    );
+  });
+
+  it("fails a choice proposal that never got options", async () => {
+    const { proposal } = await createProposal({ action: { choice: {} } });
+
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+
+    assert.deepEqual(
+      (await program.account.proposal.fetch(proposal)).status,
+      { failed: {} }
+    );
+  });
+
+  it("stakes and unstakes governance tokens", async () => {
+    const [stakeAccountPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stake"), daoPDA.toBuffer(), secondPayer.publicKey.toBuffer()],
+      program.programId
+    );
+
+    const [stakeVaultPDA] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("stake_vault"),
+        daoPDA.toBuffer(),
+        secondPayer.publicKey.toBuffer(),
+      ],
+      program.programId
+    );
+
+    const SecondUserTokenMintAccount = await getOrCreateAssociatedTokenAccount(
+      connection,
+      payer.payer,
+      governanceMintKeypair.publicKey,
+      secondPayer.publicKey
+    );
+
+    const stakeAccounts = {
+      dao: daoPDA,
+      stakeAccount: stakeAccountPDA,
+      stakeVault: stakeVaultPDA,
+      userTokenMintAccount: SecondUserTokenMintAccount.address,
+      user: secondPayer.publicKey,
+      tokenMint: governanceMintKeypair.publicKey,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+
+    // no lockup so the tokens can be unstaked right away
+    await program.methods
+      .stake(new anchor.BN(10 * DECIMALS_PER_TOKEN), new anchor.BN(0))
+      .accounts({ ...stakeAccounts, membership: membershipPDA })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    let stakeAccount = await program.account.stakeAccount.fetch(
+      stakeAccountPDA
+    );
+
+    assert.equal(stakeAccount.amount.toNumber(), 10 * DECIMALS_PER_TOKEN);
+
+    await program.methods
+      .unstake(new anchor.BN(10 * DECIMALS_PER_TOKEN))
+      .accounts(stakeAccounts)
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    stakeAccount = await program.account.stakeAccount.fetch(stakeAccountPDA);
+
+    assert.equal(stakeAccount.amount.toNumber(), 0);
+  });
+
+  it("burns the tokens sent to the burn vault", async () => {
+    await transfer(
+      connection,
+      payer.payer,
+      await tokenAccount(payer.publicKey),
+      burnPDA,
+      payer.payer,
+      5 * DECIMALS_PER_TOKEN
+    );
+
+    const supplyBefore = await tokenSupply();
+
+    await program.methods
+      .flushBurnVault()
+      .accounts({
+        dao: daoPDA,
+        burnVault: burnPDA,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    assert.equal(await tokenSupply(), supplyBefore - 5 * DECIMALS_PER_TOKEN);
+  });
+
+  it("burns the vote cost of a burn on vote proposal", async () => {
+    const { proposal, escrow } = await createProposal({ burnOnVote: true });
+
+    const supplyBefore = await tokenSupply();
+
+    await voteOnProposal(proposal, escrow, 2);
+
+    // 2 votes cost 2^2 tokens
+    assert.equal(await tokenSupply(), supplyBefore - 4 * DECIMALS_PER_TOKEN);
+  });
+
+  it("burns the treasury tokens of an executed burn proposal", async () => {
+    await updateDaoConfig({ minYesVotes: new anchor.BN(1) });
+
+    const { proposal, escrow } = await createProposal({
+      action: { burn: {} },
+      tokenAmount: new anchor.BN(1 * DECIMALS_PER_TOKEN),
+    });
+
+    await voteOnProposal(proposal, escrow, 1);
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+
+    const supplyBefore = await tokenSupply();
+
+    await executeProposal(proposal);
+
+    assert.ok((await program.account.proposal.fetch(proposal)).executed);
+    assert.equal(await tokenSupply(), supplyBefore - 1 * DECIMALS_PER_TOKEN);
+  });
+
+  it("refuses to migrate a vote record in the current layout", async () => {
+    const { proposal, escrow } = await createProposal();
+
+    await voteOnProposal(proposal, escrow, 1);
+
+    const error = await program.methods
+      .migrateVoteRecord()
+      .accounts({
+        proposal,
+        userProposalVotes: userVotesAddress(secondPayer.publicKey, proposal),
+        voter: secondPayer.publicKey,
+        user: payer.publicKey,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "VoteRecordAlreadyMigrated");
+  });
+
+  it("applies the config change of an executed update config proposal", async () => {
+    const { proposal } = await nextProposal();
+    const [configChange] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("proposal_config_change"), proposal.toBuffer()],
+      program.programId
+    );
+    const membershipFee = new anchor.BN(50 * DECIMALS_PER_TOKEN);
+
+    const { escrow } = await createProposal(
+      {
+        action: { updateConfig: {} },
+        config: configArgs({ membershipFee }),
+      },
+      { configChange }
+    );
+
+    await voteOnProposal(proposal, escrow, 1);
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+    await executeProposal(proposal, { configChange });
+
+    const dao = await program.account.dao.fetch(daoPDA);
+
+    assert.ok(dao.membershipFee.eq(membershipFee));
+  });
+
+  it("rejects update config proposals without a config change", async () => {
+    const error = await (
+      await createProposalMethod({ action: { updateConfig: {} } })
+    )
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "InvalidProposalAction");
+  });
+
+  it("executes the instructions of a passed proposal with the DAO authority", async () => {
+    const recipient = new anchor.web3.Keypair();
+    const lamports = LAMPORTS_PER_SOL / 100;
+
+    await provider.sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        anchor.web3.SystemProgram.transfer({
+          fromPubkey: payer.publicKey,
+          toPubkey: daoAuthorityPDA,
+          lamports: LAMPORTS_PER_SOL / 10,
+        })
+      )
+    );
+
+    const { proposal, escrow } = await createProposal({
+      action: { executeInstructions: {} },
+    });
+    const instruction = authorityTransfer(recipient.publicKey, lamports);
+
+    await insertProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    await voteOnProposal(proposal, escrow, 1);
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+
+    await executeProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    assert.equal(await connection.getBalance(recipient.publicKey), lamports);
+    assert.ok((await program.account.proposal.fetch(proposal)).executed);
+
+    const error = await executeProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "ProposalAlreadyExecuted");
+  });
+
+  it("refuses to execute the instructions of an active proposal", async () => {
+    const { proposal } = await createProposal({
+      action: { executeInstructions: {} },
+    });
+    const instruction = authorityTransfer(payer.publicKey, 1);
+
+    await insertProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const error = await executeProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "ProposalNotFinalized");
+  });
+
+  it("rejects new instructions once the proposal has abstain votes", async () => {
+    const { proposal, escrow } = await createProposal({
+      action: { executeInstructions: {} },
+    });
+
+    await voteOnProposal(proposal, escrow, 1, { abstain: {} });
+
+    const error = await insertProposalInstruction(
+      proposal,
+      0,
+      authorityTransfer(payer.publicKey, 1)
+    )
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "ProposalAlreadyVoted");
+  });
+
+  it("keeps queued instructions locked until the execution delay passes", async () => {
+    await updateDaoConfig({ executionDelay: new anchor.BN(3600) });
+
+    const { proposal, escrow } = await createProposal({
+      action: { executeInstructions: {} },
+    });
+    const instruction = authorityTransfer(payer.publicKey, 1);
+
+    await insertProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    await voteOnProposal(proposal, escrow, 1);
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+
+    const error = await executeProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "ProposalTimelocked");
+
+    await updateDaoConfig({ executionDelay: new anchor.BN(0) });
+  });
+
+  it("expires queued instructions after the execution window", async () => {
+    await updateDaoConfig({
+      executionDelay: new anchor.BN(1),
+      executionWindow: new anchor.BN(1),
+    });
+
+    const { proposal, escrow } = await createProposal({
+      action: { executeInstructions: {} },
+    });
+    const instruction = authorityTransfer(payer.publicKey, 1);
+
+    await insertProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    await voteOnProposal(proposal, escrow, 1);
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+
+    const { expiresAt } = await program.account.proposal.fetch(proposal);
+
+    while (
+      (await connection.getBlockTime(await connection.getSlot())) <=
+      expiresAt.toNumber()
+    ) {
+      await sleep(1000);
+    }
+
+    await executeProposalInstruction(proposal, 0, instruction)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const { status, instructionsExecuted } =
+      await program.account.proposal.fetch(proposal);
+
+    assert.deepEqual(status, { expired: {} });
+    assert.equal(instructionsExecuted, 0);
+
+    await updateDaoConfig({
+      executionDelay: new anchor.BN(0),
+      executionWindow: new anchor.BN(0),
+    });
+  });
+
+  it("lets the creator cancel a proposal before any votes", async () => {
+    const { proposalCreationFee } = await program.account.dao.fetch(daoPDA);
+    const treasuryBefore = await tokenBalance(treasuryPDA);
+
+    const { proposal } = await createProposal();
+
+    await cancelProposal(proposal)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const { status } = await program.account.proposal.fetch(proposal);
+
+    assert.deepEqual(status, { cancelled: {} });
+    // the creation fee is not refunded
+    assert.equal(
+      await tokenBalance(treasuryPDA),
+      treasuryBefore + proposalCreationFee.toNumber()
+    );
+  });
+
+  it("lets only an admin cancel a voted proposal and refunds the escrow", async () => {
+    const voterAccount = await tokenAccount(secondPayer.publicKey);
+    const { proposal, escrow } = await createProposal();
+    const balanceBefore = await tokenBalance(voterAccount);
+
+    await voteOnProposal(proposal, escrow, 2);
+
+    const error = await cancelProposal(proposal)
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "ProposalAlreadyVoted");
+
+    await cancelProposal(proposal, true)
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const { status } = await program.account.proposal.fetch(proposal);
+
+    assert.deepEqual(status, { cancelled: {} });
+
+    await program.methods
+      .withdrawVoteDeposit()
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        userProposalVotes: userVotesAddress(secondPayer.publicKey, proposal),
+        voter: secondPayer.publicKey,
+        escrowVault: escrow,
+        userTokenMintAccount: voterAccount,
+        user: secondPayer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    assert.equal(await tokenBalance(voterAccount), balanceBefore);
+  });
+
+  it("refuses to cancel a proposal whose instructions started executing", async () => {
+    const { proposal, escrow } = await createProposal({
+      action: { executeInstructions: {} },
+    });
+    const instructions = [
+      authorityTransfer(payer.publicKey, 1),
+      authorityTransfer(payer.publicKey, 1),
+    ];
+
+    for (const [index, instruction] of instructions.entries()) {
+      await insertProposalInstruction(proposal, index, instruction)
+        .rpc()
+        .catch((e) => {
+          console.log(e);
+        });
+    }
+
+    await voteOnProposal(proposal, escrow, 1);
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+
+    await executeProposalInstruction(proposal, 0, instructions[0])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const error = await cancelProposal(proposal, true)
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "ProposalAlreadyExecuted");
+  });
+
+  it("admits allowlisted wallets only", async () => {
+    await updateDaoConfig({ admissionPolicy: { allowlist: {} } });
+
+    const member = await newMember();
+
+    const error = await joinDao(member).catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "NotAdmitted");
+
+    await addAdmissionEntry(member.publicKey);
+    await joinDao(member, [], {
+      admissionEntry: admissionEntryAddress(member.publicKey),
+    }).catch((e) => {
+      console.log(e);
+    });
+
+    assert.ok(await isMember(member.publicKey));
+  });
+
+  it("admits wallets attested by the configured issuer", async () => {
+    const issuer = await newMember();
+
+    await updateDaoConfig({
+      admissionPolicy: { attestation: {} },
+      admissionKey: issuer.publicKey,
+    });
+
+    const member = await newMember();
+
+    await addAdmissionEntry(member.publicKey, issuer);
+    await joinDao(member, [], {
+      admissionEntry: admissionEntryAddress(member.publicKey),
+    }).catch((e) => {
+      console.log(e);
+    });
+
+    assert.ok(await isMember(member.publicKey));
+  });
+
+  it("admits wallets with a proof of the merkle allowlist", async () => {
+    const error = await program.methods
+      .updateDaoConfig(
+        configArgs({
+          admissionPolicy: { merkleAllowlist: {} },
+          admissionRoot: Array(32).fill(0),
+        })
+      )
+      .accounts({
+        dao: daoPDA,
+        admin: adminPDA,
+        user: payer.publicKey,
+      })
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "InvalidAdmissionPolicy");
+
+    const [member, outsider] = [await newMember(), await newMember()];
+    const leaves = [member.publicKey, payer.publicKey].map((wallet) =>
+      Buffer.from(keccak_256(wallet.toBuffer()))
+    );
+    const root = keccak_256(Buffer.concat(leaves.sort(Buffer.compare)));
+    const proof = [
+      Array.from(Buffer.from(keccak_256(payer.publicKey.toBuffer()))),
+    ];
+
+    await updateDaoConfig({
+      admissionPolicy: { merkleAllowlist: {} },
+      admissionRoot: Array.from(root),
+    });
+
+    const outsiderError = await joinDao(outsider, proof).catch((e) => e);
+
+    assert.equal(outsiderError.error.errorCode.code, "NotAdmitted");
+
+    await joinDao(member, proof).catch((e) => {
+      console.log(e);
+    });
+
+    assert.ok(await isMember(member.publicKey));
+  });
+
+  it("refuses NFTs without verified collection metadata", async () => {
+    const holder = await newMember();
+    const { mint, ownerAccount } = await createNft(holder.publicKey);
+
+    await updateDaoConfig({
+      admissionPolicy: { collectionHolding: {} },
+      admissionKey: new anchor.web3.Keypair().publicKey,
+    });
+
+    const [metadata] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("metadata"), METADATA_PROGRAM_ID.toBuffer(), mint.toBuffer()],
+      METADATA_PROGRAM_ID
+    );
+
+    // the NFT was never registered with the metadata program
+    const error = await joinDao(holder, [], {
+      admissionTokenAccount: ownerAccount,
+      admissionMetadata: metadata,
+      admissionClaim: admissionClaimAddress(mint),
+    }).catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "NotAdmitted");
+
+    await updateDaoConfig({ admissionPolicy: { open: {} } });
+  });
+
+  it("votes with the weight of a snapshot fixed at creation", async () => {
+    // 2 votes cost 2^2 tokens of the snapshot weight
+    const weight = new anchor.BN(4 * DECIMALS_PER_TOKEN);
+    const snapshotRoot = Array.from(
+      keccak_256(
+        Buffer.concat([
+          secondPayer.publicKey.toBuffer(),
+          weight.toArrayLike(Buffer, "le", 8),
+        ])
+      )
+    );
+
+    // the snapshot gives wallets their voting weight, members can't pick it
+    const memberError = await (
+      await createProposalMethod({ snapshotRoot })
+    )
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(memberError.error.errorCode.code, "NotAuthorized");
+
+    const { proposal, escrow } = await nextProposal();
+
+    await program.methods
+      .createProposal({
+        tokenAmount: new anchor.BN(0),
+        endDate: new anchor.BN(Math.floor(Date.now() / 1000) + 5),
+        title: "Snapshot proposal.",
+        description: "Proposal voted on with the weights of a snapshot.",
+        action: { transfer: {} },
+        burnOnVote: false,
+        snapshotRoot,
+        config: null,
+      })
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        configChange: null,
+        treasuryVault: treasuryPDA,
+        escrowVault: escrow,
+        beneficiary: await tokenAccount(beneficiary.publicKey),
+        beneficiaryOwner: beneficiary.publicKey,
+        membership: membershipAddress(payer.publicKey),
+        admin: adminPDA,
+        user: payer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        userTokenMintAccount: await tokenAccount(payer.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    assert.deepEqual(
+      (await program.account.proposal.fetch(proposal)).snapshotRoot,
+      snapshotRoot
+    );
+
+    const voteWithSnapshot = (amount: number) =>
+      program.methods
+        .voteWithSnapshot(weight, [], new anchor.BN(amount), { yes: {} })
+        .accounts({
+          dao: daoPDA,
+          proposal,
+          userProposalVotes: userVotesAddress(secondPayer.publicKey, proposal),
+          voiceCredits: null,
+          user: secondPayer.publicKey,
+          tokenMint: governanceMintKeypair.publicKey,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers([secondPayer])
+        .rpc();
+
+    await voteWithSnapshot(2).catch((e) => {
+      console.log(e);
+    });
+
+    assert.equal(
+      (await program.account.proposal.fetch(proposal)).yesVotes.toNumber(),
+      2
+    );
+
+    const weightError = await voteWithSnapshot(1).catch((e) => e);
+
+    assert.equal(weightError.error.errorCode.code, "SnapshotWeightExceeded");
+
+    // votes cast with a snapshot proof are final
+    const switchError = await program.methods
+      .switchVoteSide({ no: {} }, new anchor.BN(0))
+      .accounts({
+        dao: daoPDA,
+        proposal,
+        userProposalVotes: userVotesAddress(secondPayer.publicKey, proposal),
+        escrowVault: escrow,
+        userTokenMintAccount: await tokenAccount(secondPayer.publicKey),
+        membership: membershipPDA,
+        voiceCredits: null,
+        user: secondPayer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(switchError.error.errorCode.code, "SnapshotVoteRequired");
+  });
+
+  it("refuses to delegate to a wallet outside the DAO", async () => {
+    const error = await delegateMethod(
+      secondPayer,
+      new anchor.web3.Keypair().publicKey
+    )
+      .rpc()
+      .catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "AccountNotInitialized");
+  });
+
+  it("prices the votes of a delegate per delegator", async () => {
+    const member = await newMember();
+
+    await joinDao(member).catch((e) => {
+      console.log(e);
+    });
+
+    const delegators = [secondPayer, member];
+
+    for (const delegator of delegators) {
+      await delegateMethod(delegator, payer.publicKey)
+        .rpc()
+        .catch((e) => {
+          console.log(e);
+        });
+    }
+
+    const { proposal, escrow } = await createProposal();
+    const payerAccount = await tokenAccount(payer.publicKey);
+    const balanceBefore = await tokenBalance(payerAccount);
+
+    for (const delegator of delegators) {
+      await program.methods
+        .voteAsDelegate(new anchor.BN(2), { yes: {} })
+        .accounts({
+          dao: daoPDA,
+          proposal,
+          delegation: delegationAddress(delegator.publicKey),
+          delegator: delegator.publicKey,
+          userProposalVotes: userVotesAddress(delegator.publicKey, proposal),
+          escrowVault: escrow,
+          userTokenMintAccount: payerAccount,
+          membership: membershipAddress(delegator.publicKey),
+          delegateMembership: membershipAddress(payer.publicKey),
+          voiceCredits: null,
+          user: payer.publicKey,
+          tokenMint: governanceMintKeypair.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .rpc()
+        .catch((e) => {
+          console.log(e);
+        });
+    }
+
+    // 2 votes for each delegator cost 2 * 2^2 tokens instead of 4^2 for the pooled votes
+    assert.equal(
+      await tokenBalance(payerAccount),
+      balanceBefore - 8 * DECIMALS_PER_TOKEN
+    );
+    assert.equal(
+      (await program.account.proposal.fetch(proposal)).yesVotes.toNumber(),
+      4
+    );
+  });
+
+  it("returns empty vote records to their voter only", async () => {
+    const { proposal, escrow } = await createProposal();
+    const userProposalVotes = userVotesAddress(secondPayer.publicKey, proposal);
+
+    await voteOnProposal(proposal, escrow, 0);
+    await waitForEnd(proposal);
+    await finalizeProposal(proposal);
+
+    const withdrawVoteDeposit = async (user: anchor.web3.Keypair) =>
+      program.methods
+        .withdrawVoteDeposit()
+        .accounts({
+          dao: daoPDA,
+          proposal,
+          userProposalVotes,
+          voter: secondPayer.publicKey,
+          escrowVault: escrow,
+          userTokenMintAccount: await tokenAccount(user.publicKey),
+          user: user.publicKey,
+          tokenMint: governanceMintKeypair.publicKey,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: anchor.web3.SystemProgram.programId,
+        })
+        .signers(user === payer.payer ? [] : [user])
+        .rpc();
+
+    const error = await withdrawVoteDeposit(payer.payer).catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "VotePayerMismatch");
+
+    await withdrawVoteDeposit(secondPayer).catch((e) => {
+      console.log(e);
+    });
+
+    assert.equal(await connection.getAccountInfo(userProposalVotes), null);
+  });
+
+  it("refuses stake votes whose lockup ends before the proposal", async () => {
+    const member = await newMember();
+
+    await joinDao(member).catch((e) => {
+      console.log(e);
+    });
+
+    const voteWithStake = await stakeFor(member, 10 * DECIMALS_PER_TOKEN, 0);
+    const { proposal } = await createProposal();
+
+    const error = await voteWithStake(proposal, 1).catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "InvalidLockUp");
+  });
+
+  it("limits stake votes to the weight of the stake", async () => {
+    const member = await newMember();
+
+    await joinDao(member).catch((e) => {
+      console.log(e);
+    });
+
+    // 4 staked tokens pay for 2 votes, the short lockup adds almost no boost
+    const voteWithStake = await stakeFor(member, 4 * DECIMALS_PER_TOKEN, 3600);
+    const { proposal } = await createProposal();
+
+    await voteWithStake(proposal, 2).catch((e) => {
+      console.log(e);
+    });
+
+    assert.equal(
+      (await program.account.proposal.fetch(proposal)).yesVotes.toNumber(),
+      2
+    );
+
+    const error = await voteWithStake(proposal, 1).catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "InsufficientStake");
+  });
+
+  it("charges voice credits for stake votes", async () => {
+    const member = await newMember();
+
+    await joinDao(member).catch((e) => {
+      console.log(e);
+    });
+
+    // 2 votes cost 4 credits, one more than the budget of the proposal
+    await updateDaoConfig({
+      creditMode: { perProposal: {} },
+      creditAllowance: new anchor.BN(3),
+    });
+
+    const voteWithStake = await stakeFor(member, 4 * DECIMALS_PER_TOKEN, 3600);
+    const { proposal } = await createProposal();
+
+    const error = await voteWithStake(proposal, 2).catch((e) => e);
+
+    assert.equal(error.error.errorCode.code, "InsufficientVoiceCredits");
+
+    await voteWithStake(proposal, 1).catch((e) => {
+      console.log(e);
+    });
+
+    assert.equal(
+      (await program.account.proposal.fetch(proposal)).yesVotes.toNumber(),
+      1
+    );
+
+    await updateDaoConfig({ creditMode: { disabled: {} } });
+  });
+
+  it("migrates a legacy proposal and votes on it", async function () {
+    // tests/fixtures/legacy_proposal.json, loaded by the local validator
+    const legacyProposal = new PublicKey(
+      "CZLQSD4fPDeCFw7ewyKHpt3H9RVu4rP3haZpi3sAWxyJ"
+    );
+
+    if ((await connection.getAccountInfo(legacyProposal)) === null) {
+      this.skip();
+    }
+
+    // the legacy proposal belongs to the first DAO of the second payer
+    const seeds = (...buffers: Buffer[]) =>
+      anchor.web3.PublicKey.findProgramAddressSync(buffers, program.programId)[0];
+    const legacyDao = seeds(
+      Buffer.from("dao"),
+      secondPayer.publicKey.toBuffer(),
+      new anchor.BN(0).toBuffer("le", 8)
+    );
+    const legacyMembership = seeds(
+      Buffer.from("membership"),
+      legacyDao.toBuffer(),
+      secondPayer.publicKey.toBuffer()
+    );
+    const escrowVault = seeds(
+      Buffer.from("escrow_vault"),
+      legacyProposal.toBuffer()
+    );
+
+    await program.methods
+      .createDao(
+        "Legacy DAO",
+        "https://quadra.test/legacy.png",
+        new anchor.BN(1),
+        new anchor.BN(1 * DECIMALS_PER_TOKEN),
+        new anchor.BN(1 * DECIMALS_PER_TOKEN),
+        0
+      )
+      .accounts({
+        registry: seeds(
+          Buffer.from("creator_registry"),
+          secondPayer.publicKey.toBuffer()
+        ),
+        dao: legacyDao,
+        admin: seeds(
+          Buffer.from("admin"),
+          legacyDao.toBuffer(),
+          secondPayer.publicKey.toBuffer()
+        ),
+        membership: legacyMembership,
+        treasuryVault: seeds(Buffer.from("treasury_vault"), legacyDao.toBuffer()),
+        burnVault: seeds(Buffer.from("burn"), legacyDao.toBuffer()),
+        feeAccount: feePDA,
+        user: secondPayer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    await program.methods
+      .migrateProposal()
+      .accounts({
+        dao: legacyDao,
+        proposal: legacyProposal,
+        escrowVault,
+        user: payer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    await program.methods
+      .voteOnProposal(new anchor.BN(1), { yes: {} })
+      .accounts({
+        dao: legacyDao,
+        proposal: legacyProposal,
+        userProposalVotes: userVotesAddress(
+          secondPayer.publicKey,
+          legacyProposal
+        ),
+        escrowVault,
+        userTokenMintAccount: await tokenAccount(secondPayer.publicKey),
+        membership: legacyMembership,
+        voiceCredits: null,
+        user: secondPayer.publicKey,
+        tokenMint: governanceMintKeypair.publicKey,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: anchor.web3.SystemProgram.programId,
+      })
+      .signers([secondPayer])
+      .rpc()
+      .catch((e) => {
+        console.log(e);
+      });
+
+    const proposal = await program.account.proposal.fetch(legacyProposal);
+
+    assert.equal(proposal.version, 1);
+    assert.equal(proposal.yesVotes.toNumber(), 1);
+    assert.equal(await tokenBalance(escrowVault), 1 * DECIMALS_PER_TOKEN);
+  });
 });